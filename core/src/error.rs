@@ -0,0 +1,62 @@
+use std::fmt;
+
+/// Why a tree could not be saved, loaded or parsed.
+#[derive(Clone, Debug, PartialEq)]
+pub enum NodeError {
+    /// The storage backend cannot be used at all, or a call to it failed.
+    StorageUnavailable(String),
+    /// The storage backend has no room left for the value.
+    QuotaExceeded,
+    /// Nothing is saved under the key.
+    KeyMissing(String),
+    /// The document was written by a newer version of the schema.
+    UnsupportedVersion(u64),
+    /// The saved value is not valid JSON.
+    JsonSyntax(String),
+    /// The imported file is not valid XML.
+    XmlSyntax(String),
+    /// The JSON is well-formed but not a tree. `path` points at the offending
+    /// value, e.g. `$.children[2].text`.
+    Schema { path: String, message: String },
+}
+
+impl NodeError {
+    pub fn schema(path: &str, message: impl Into<String>) -> Self {
+        Self::Schema {
+            path: path.to_string(),
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for NodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NodeError::StorageUnavailable(message) => write!(f, "Storage unavailable: {}", message),
+            NodeError::QuotaExceeded => write!(f, "Storage quota exceeded"),
+            NodeError::KeyMissing(key) => write!(f, "No item found with key: {}", key),
+            NodeError::UnsupportedVersion(version) => {
+                write!(f, "Saved by a newer version (schema version {})", version)
+            }
+            NodeError::JsonSyntax(message) => write!(f, "Failed to parse JSON: {}", message),
+            NodeError::XmlSyntax(message) => write!(f, "Failed to parse XML: {}", message),
+            NodeError::Schema { path, message } => {
+                write!(f, "Invalid tree at {}: {}", path, message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for NodeError {}
+
+impl From<serde_json::Error> for NodeError {
+    fn from(err: serde_json::Error) -> Self {
+        NodeError::JsonSyntax(err.to_string())
+    }
+}
+
+impl From<roxmltree::Error> for NodeError {
+    fn from(err: roxmltree::Error) -> Self {
+        NodeError::XmlSyntax(err.to_string())
+    }
+}
@@ -0,0 +1,645 @@
+use serde_json::{json, Value};
+
+use crate::{merge_text, NodeData, NodeId};
+
+/// A single change to the document, mirroring a row of the `events` table.
+///
+/// `to_json` produces the `type`/`data` pair stored in that table, so the
+/// same values can be written to the server or replayed locally.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Event {
+    /// A new node was added at `index` among the children of `parent`.
+    /// `parent` is `None` for children of the root. `after` is the sibling
+    /// it was added after, if any, see `NodeData::anchor`.
+    Added {
+        id: NodeId,
+        parent: Option<NodeId>,
+        index: usize,
+        after: Option<NodeId>,
+        text: String,
+    },
+    /// The text of the node was set to `text`. `from` is the text it
+    /// replaced, so edits made concurrently can be merged.
+    Edited {
+        id: NodeId,
+        text: String,
+        from: Option<String>,
+    },
+    /// `at` is the completion time in milliseconds since the Unix epoch,
+    /// `None` for events recorded before it was tracked.
+    MarkedAsDone {
+        id: NodeId,
+        at: Option<u64>,
+    },
+    MarkedAsUndone {
+        id: NodeId,
+    },
+    /// The node and its whole subtree were removed from their parent.
+    Removed {
+        id: NodeId,
+    },
+    /// The node and its subtree were moved to `index` among the children of
+    /// `parent`, counted after detaching it from its old position, right
+    /// after the sibling `after` if it is still there.
+    Moved {
+        id: NodeId,
+        parent: Option<NodeId>,
+        index: usize,
+        after: Option<NodeId>,
+    },
+    /// The children of the node were shown (`open`) or hidden.
+    Folded {
+        id: NodeId,
+        open: bool,
+    },
+}
+
+impl Event {
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Event::Added { .. } => "Added",
+            Event::Edited { .. } => "Edited",
+            Event::MarkedAsDone { .. } => "MarkedAsDone",
+            Event::MarkedAsUndone { .. } => "MarkedAsUndone",
+            Event::Removed { .. } => "Removed",
+            Event::Moved { .. } => "Moved",
+            Event::Folded { .. } => "Folded",
+        }
+    }
+
+    pub fn data(&self) -> Value {
+        match self {
+            Event::Added {
+                id,
+                parent,
+                index,
+                after,
+                text,
+            } => json!({
+                "id": id.to_string(),
+                "parent": parent.map(|parent| parent.to_string()),
+                "index": index,
+                "after": after.map(|after| after.to_string()),
+                "text": text
+            }),
+            Event::Edited { id, text, from } => json!({
+                "id": id.to_string(),
+                "text": text,
+                "from": from
+            }),
+            Event::MarkedAsDone { id, at } => json!({
+                "id": id.to_string(),
+                "at": at
+            }),
+            Event::MarkedAsUndone { id } | Event::Removed { id } => json!({ "id": id.to_string() }),
+            Event::Moved {
+                id,
+                parent,
+                index,
+                after,
+            } => json!({
+                "id": id.to_string(),
+                "parent": parent.map(|parent| parent.to_string()),
+                "index": index,
+                "after": after.map(|after| after.to_string())
+            }),
+            Event::Folded { id, open } => json!({
+                "id": id.to_string(),
+                "open": open
+            }),
+        }
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "type": self.type_name(),
+            "data": self.data()
+        })
+    }
+
+    pub fn from_json(value: &Value) -> Option<Self> {
+        Self::from_parts(value["type"].as_str()?, &value["data"])
+    }
+
+    /// Rewrites a `parent` of `root` to `None`, the form stored in the event
+    /// log, so events do not depend on the id of the local root node.
+    pub fn relative_to(self, root: NodeId) -> Self {
+        let unparent = |parent: Option<NodeId>| parent.filter(|&parent| parent != root);
+        match self {
+            Event::Added {
+                id,
+                parent,
+                index,
+                after,
+                text,
+            } => Event::Added {
+                id,
+                parent: unparent(parent),
+                index,
+                after,
+                text,
+            },
+            Event::Moved {
+                id,
+                parent,
+                index,
+                after,
+            } => Event::Moved {
+                id,
+                parent: unparent(parent),
+                index,
+                after,
+            },
+            event => event,
+        }
+    }
+
+    /// Rewrites an event of the node `root` to one of the nil id, which
+    /// stands for the root in the events on the server: each device made its
+    /// root with an id of its own.
+    pub fn to_shared_root(self, root: NodeId) -> Self {
+        self.retarget(root, NodeId::nil())
+    }
+
+    /// Rewrites an event of the nil id, the root on the server, to one of
+    /// the local `root`. See `to_shared_root`.
+    pub fn from_shared_root(self, root: NodeId) -> Self {
+        self.retarget(NodeId::nil(), root)
+    }
+
+    /// The event with `to` as its node if that is `from`.
+    fn retarget(mut self, from: NodeId, to: NodeId) -> Self {
+        match &mut self {
+            Event::Added { id, .. }
+            | Event::Edited { id, .. }
+            | Event::MarkedAsDone { id, .. }
+            | Event::MarkedAsUndone { id }
+            | Event::Removed { id }
+            | Event::Moved { id, .. }
+            | Event::Folded { id, .. } => {
+                if *id == from {
+                    *id = to;
+                }
+            }
+        }
+        self
+    }
+
+    /// Builds an event from the `type` and `data` columns of an `events` row.
+    pub fn from_parts(type_name: &str, data: &Value) -> Option<Self> {
+        let id = parse_id(&data["id"])?;
+
+        match type_name {
+            "Added" => {
+                let parent = parse_parent(&data["parent"])?;
+                // Rows written before positions were recorded always prepended.
+                let index = data["index"].as_u64().unwrap_or(0).try_into().ok()?;
+                let text = data["text"].as_str()?.to_string();
+                Some(Event::Added {
+                    id,
+                    parent,
+                    index,
+                    after: parse_id(&data["after"]),
+                    text,
+                })
+            }
+            "Edited" => {
+                let text = data["text"].as_str()?.to_string();
+                let from = data["from"].as_str().map(str::to_string);
+                Some(Event::Edited { id, text, from })
+            }
+            "MarkedAsDone" => Some(Event::MarkedAsDone {
+                id,
+                at: data["at"].as_u64(),
+            }),
+            "MarkedAsUndone" => Some(Event::MarkedAsUndone { id }),
+            "Removed" => Some(Event::Removed { id }),
+            "Moved" => Some(Event::Moved {
+                id,
+                parent: parse_parent(&data["parent"])?,
+                index: data["index"].as_u64()?.try_into().ok()?,
+                after: parse_id(&data["after"]),
+            }),
+            "Folded" => Some(Event::Folded {
+                id,
+                open: data["open"].as_bool()?,
+            }),
+            _ => None,
+        }
+    }
+}
+
+fn parse_id(value: &Value) -> Option<NodeId> {
+    NodeId::parse_str(value.as_str()?).ok()
+}
+
+/// Parses a `parent` field, where `null` stands for the root.
+fn parse_parent(value: &Value) -> Option<Option<NodeId>> {
+    match value {
+        Value::Null => Some(None),
+        parent => Some(Some(parse_id(parent)?)),
+    }
+}
+
+impl NodeData {
+    /// Applies `event` to the tree rooted at `self`.
+    ///
+    /// Returns `false` and leaves the tree untouched if the event refers to a
+    /// node that does not exist (or, for `Added`, to an id that already does).
+    pub fn apply(&mut self, event: &Event) -> bool {
+        match event {
+            Event::Added {
+                id,
+                parent,
+                index,
+                after,
+                text,
+            } => {
+                if self.find(*id).is_some() {
+                    return false;
+                }
+                let parent = match parent {
+                    Some(parent_id) => self.find_mut(*parent_id),
+                    None => Some(self),
+                };
+                match parent {
+                    Some(parent) => {
+                        let index = parent.insertion_index(*id, *after, *index);
+                        parent.insert_child_at(index, NodeData::with_id(*id, false, text, vec![]));
+                        true
+                    }
+                    None => false,
+                }
+            }
+            Event::Edited { id, text, from } => match self.find_mut(*id) {
+                Some(node) => {
+                    node.text = match from {
+                        Some(from) => merge_text(from, &node.text, text),
+                        None => text.clone(),
+                    };
+                    true
+                }
+                None => false,
+            },
+            Event::MarkedAsDone { id, at } => match self.find_mut(*id) {
+                Some(node) => {
+                    node.set_done(true, *at);
+                    true
+                }
+                None => false,
+            },
+            Event::MarkedAsUndone { id } => match self.find_mut(*id) {
+                Some(node) => {
+                    node.set_done(false, None);
+                    true
+                }
+                None => false,
+            },
+            Event::Removed { id } => match self.find_parent_mut(*id) {
+                Some(parent) => parent.remove_child(*id).is_some(),
+                None => false,
+            },
+            Event::Moved {
+                id,
+                parent,
+                index,
+                after,
+            } => {
+                let parent = parent.unwrap_or(self.id);
+                let index = self
+                    .find(parent)
+                    .map_or(*index, |parent| parent.insertion_index(*id, *after, *index));
+                self.move_child(*id, parent, index)
+            }
+            Event::Folded { id, open } => match self.find_mut(*id) {
+                Some(node) => {
+                    node.is_open = *open;
+                    true
+                }
+                None => false,
+            },
+        }
+    }
+
+    /// Events that recreate the descendants of this node, and their
+    /// completion and fold state, when applied to an empty node with the
+    /// same id.
+    pub fn to_events(&self) -> Vec<Event> {
+        let mut events = Vec::new();
+        for (index, child) in self.children.iter().enumerate() {
+            child.push_subtree_events(None, index, &mut events);
+        }
+        events
+    }
+
+    /// Pushes the events that add this node and its subtree at `index` among
+    /// the children of `parent`.
+    pub fn push_subtree_events(
+        &self,
+        parent: Option<NodeId>,
+        index: usize,
+        events: &mut Vec<Event>,
+    ) {
+        events.push(Event::Added {
+            id: self.id,
+            parent,
+            index,
+            after: None,
+            text: self.text.clone(),
+        });
+        if self.done {
+            events.push(Event::MarkedAsDone {
+                id: self.id,
+                at: self.done_at,
+            });
+        }
+        if self.is_open {
+            events.push(Event::Folded {
+                id: self.id,
+                open: true,
+            });
+        }
+        for (index, child) in self.children.iter().enumerate() {
+            child.push_subtree_events(Some(self.id), index, events);
+        }
+    }
+
+    /// Applies `events` in order, skipping any that do not apply.
+    pub fn replay<'a>(&mut self, events: impl IntoIterator<Item = &'a Event>) {
+        for event in events {
+            self.apply(event);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_json_roundtrip() {
+        let lunch = NodeData::next_id();
+        let pasta = NodeData::next_id();
+        let events = vec![
+            Event::Added {
+                id: lunch,
+                parent: None,
+                index: 0,
+                after: None,
+                text: "make lunch".to_string(),
+            },
+            Event::Added {
+                id: pasta,
+                parent: Some(lunch),
+                index: 0,
+                after: None,
+                text: "cook pasta".to_string(),
+            },
+            Event::Added {
+                id: NodeData::next_id(),
+                parent: Some(lunch),
+                index: 1,
+                after: Some(pasta),
+                text: "add pesto".to_string(),
+            },
+            Event::Edited {
+                id: lunch,
+                text: "make pasta for lunch".to_string(),
+                from: None,
+            },
+            Event::Edited {
+                id: lunch,
+                text: "make pasta for lunch".to_string(),
+                from: Some("make lunch".to_string()),
+            },
+            Event::MarkedAsDone {
+                id: lunch,
+                at: Some(1_700_000_000_000),
+            },
+            Event::MarkedAsDone { id: lunch, at: None },
+            Event::MarkedAsUndone { id: lunch },
+            Event::Moved {
+                id: pasta,
+                parent: None,
+                index: 1,
+                after: None,
+            },
+            Event::Moved {
+                id: pasta,
+                parent: None,
+                index: 1,
+                after: Some(lunch),
+            },
+            Event::Removed { id: pasta },
+            Event::Folded {
+                id: lunch,
+                open: true,
+            },
+        ];
+
+        for event in events {
+            assert_eq!(Event::from_json(&event.to_json()), Some(event));
+        }
+    }
+
+    #[test]
+    fn test_from_parts_matches_events_table() {
+        // Rows from db/test-data.sql
+        let lunch = NodeId::parse_str("8722655e-f231-11ef-8932-1f1e2ee24d96").unwrap();
+        let pasta = NodeId::parse_str("1302f702-f23a-11ef-a65c-63c495723c09").unwrap();
+
+        let data = json!({ "parent": null, "text": "make lunch", "id": "8722655e-f231-11ef-8932-1f1e2ee24d96" });
+        assert_eq!(
+            Event::from_parts("Added", &data),
+            Some(Event::Added {
+                id: lunch,
+                parent: None,
+                index: 0,
+                after: None,
+                text: "make lunch".to_string()
+            })
+        );
+
+        let data = json!({ "text": "cook pasta", "parent": "8722655e-f231-11ef-8932-1f1e2ee24d96", "id": "1302f702-f23a-11ef-a65c-63c495723c09" });
+        assert_eq!(
+            Event::from_parts("Added", &data),
+            Some(Event::Added {
+                id: pasta,
+                parent: Some(lunch),
+                index: 0,
+                after: None,
+                text: "cook pasta".to_string()
+            })
+        );
+
+        let data = json!({ "id": "8722655e-f231-11ef-8932-1f1e2ee24d96" });
+        assert_eq!(
+            Event::from_parts("MarkedAsDone", &data),
+            Some(Event::MarkedAsDone { id: lunch, at: None })
+        );
+
+        assert!(Event::from_parts("Unknown", &data).is_none());
+        assert!(Event::from_parts("Edited", &data).is_none());
+        assert!(Event::from_parts("Removed", &json!({ "id": 7 })).is_none());
+    }
+
+    #[test]
+    fn test_apply_added_edited_removed() {
+        let mut root = NodeData::new(true, "root", vec![]);
+        let child_id = NodeData::next_id();
+
+        assert!(root.apply(&Event::Added {
+            id: child_id,
+            parent: None,
+            index: 0,
+            after: None,
+            text: "first".to_string(),
+        }));
+        assert!(root.apply(&Event::Edited {
+            id: child_id,
+            text: "edited".to_string(),
+            from: None,
+        }));
+
+        assert_eq!(root.children.len(), 1);
+        assert_eq!(root.children[0].id, child_id);
+        assert_eq!(root.children[0].text, "edited");
+
+        assert!(root.apply(&Event::Removed { id: child_id }));
+        assert!(root.children.is_empty());
+    }
+
+    #[test]
+    fn test_apply_marked_as_done_and_undone() {
+        let child = NodeData::new(false, "child", vec![]);
+        let id = child.id;
+        let mut root = NodeData::new(true, "root", vec![child]);
+
+        assert!(root.apply(&Event::MarkedAsDone { id, at: Some(42) }));
+        assert!(root.children[0].done);
+        assert_eq!(root.children[0].done_at, Some(42));
+
+        assert!(root.apply(&Event::MarkedAsUndone { id }));
+        assert!(!root.children[0].done);
+        assert_eq!(root.children[0].done_at, None);
+    }
+
+    #[test]
+    fn test_apply_added_at_index_and_moved() {
+        let a = NodeData::new(false, "A", vec![]);
+        let b = NodeData::new(false, "B", vec![]);
+        let (a, b, mut root) = (a.id, b.id, NodeData::new(true, "root", vec![a, b]));
+        let c = NodeData::next_id();
+
+        assert!(root.apply(&Event::Added {
+            id: c,
+            parent: None,
+            index: 1,
+            after: None,
+            text: "C".to_string(),
+        }));
+        let texts = |node: &NodeData| {
+            node.children
+                .iter()
+                .map(|child| child.text.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(texts(&root), ["A", "C", "B"]);
+
+        assert!(root.apply(&Event::Moved {
+            id: c,
+            parent: Some(b),
+            index: 0,
+            after: None,
+        }));
+        assert_eq!(texts(&root), ["A", "B"]);
+        assert_eq!(texts(root.find(b).unwrap()), ["C"]);
+
+        assert!(root.apply(&Event::Moved {
+            id: c,
+            parent: None,
+            index: 0,
+            after: None,
+        }));
+        assert_eq!(texts(&root), ["C", "A", "B"]);
+
+        assert!(!root.apply(&Event::Moved {
+            id: a,
+            parent: Some(a),
+            index: 0,
+            after: None,
+        }));
+    }
+
+    #[test]
+    fn test_shared_root() {
+        let mut here = NodeData::new(false, "", vec![]);
+        let mut there = NodeData::new(false, "", vec![]);
+        let edited = Event::Edited {
+            id: here.id,
+            text: "groceries".to_string(),
+            from: None,
+        };
+
+        let shared = edited.clone().to_shared_root(here.id);
+        assert_eq!(shared.to_json()["data"]["id"], NodeId::nil().to_string());
+        assert_eq!(shared.clone().from_shared_root(here.id), edited);
+        assert!(there.apply(&shared.from_shared_root(there.id)));
+        assert!(here.apply(&edited));
+        assert_eq!(there.text, here.text);
+
+        // Events of other nodes are left as they are
+        let child = Event::Folded {
+            id: NodeData::next_id(),
+            open: true,
+        };
+        assert_eq!(child.clone().to_shared_root(here.id), child);
+        assert_eq!(child.clone().from_shared_root(here.id), child);
+    }
+
+    #[test]
+    fn test_apply_rejects_unknown_ids() {
+        let mut root = NodeData::new(true, "root", vec![]);
+        let missing = NodeData::next_id();
+        let before = root.clone();
+
+        assert!(!root.apply(&Event::Edited {
+            id: missing,
+            text: "nope".to_string(),
+            from: None,
+        }));
+        assert!(!root.apply(&Event::Removed { id: missing }));
+        assert!(!root.apply(&Event::Removed { id: root.id }));
+        assert!(!root.apply(&Event::Added {
+            id: NodeData::next_id(),
+            parent: Some(missing),
+            index: 0,
+            after: None,
+            text: String::new(),
+        }));
+        assert!(!root.apply(&Event::Added {
+            id: root.id,
+            parent: None,
+            index: 0,
+            after: None,
+            text: String::new(),
+        }));
+        assert_eq!(root, before);
+    }
+
+    #[test]
+    fn test_to_events_recreates_tree() {
+        let mut grandchild = NodeData::new(false, "Grandchild", vec![]);
+        grandchild.set_done(true, Some(3));
+        let child = NodeData::new(false, "Child", vec![grandchild]);
+        let sibling = NodeData::new(false, "Sibling", vec![]);
+        let root = NodeData::new(true, "Root", vec![child, sibling]);
+
+        let events = root.to_events();
+        assert_eq!(events.len(), 4);
+
+        let mut rebuilt = NodeData::with_id(root.id, true, "Root", vec![]);
+        rebuilt.replay(events.iter());
+        assert_eq!(rebuilt, root);
+    }
+}
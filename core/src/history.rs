@@ -0,0 +1,315 @@
+use crate::{Event, NodeData, NodeId};
+
+/// Edits of the same node less than this many milliseconds apart are undone
+/// together, so undo reverts a burst of typing rather than one character.
+const TYPING_BURST_MS: u64 = 1000;
+
+/// One undoable step: the events it dispatched and the events that revert
+/// them, in the order they have to be dispatched.
+#[derive(Clone, Debug, Default, PartialEq)]
+struct Step {
+    forward: Vec<Event>,
+    backward: Vec<Event>,
+    /// The node being typed into and the time of the last edit, while the
+    /// step consists of text edits only and can take more of them.
+    typing: Option<(NodeId, u64)>,
+}
+
+/// The undo and redo stacks of an `EventLog`.
+#[derive(Clone, Debug, Default)]
+pub struct History {
+    undo: Vec<Step>,
+    redo: Vec<Step>,
+    /// Nesting depth of open transactions, whose events form a single step.
+    depth: usize,
+}
+
+impl History {
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    /// Starts a transaction: everything recorded until the matching `end` is
+    /// undone in one step.
+    pub fn begin(&mut self) {
+        if self.depth == 0 {
+            self.undo.push(Step::default());
+        }
+        self.depth += 1;
+    }
+
+    pub fn end(&mut self) {
+        self.depth = self.depth.saturating_sub(1);
+        if self.depth == 0 && self.undo.last().is_some_and(|step| step.forward.is_empty()) {
+            self.undo.pop();
+        }
+    }
+
+    /// Records that `forward` was dispatched at time `at`, and that the
+    /// `backward` events revert it. Clears the redo stack.
+    pub fn record(&mut self, forward: Event, backward: Vec<Event>, at: u64) {
+        self.redo.clear();
+        let typing = match forward {
+            Event::Edited { id, .. } if self.depth == 0 => Some((id, at)),
+            _ => None,
+        };
+
+        if let Some(step) = self.undo.last_mut() {
+            if self.depth > 0 {
+                step.forward.push(forward);
+                step.backward.splice(0..0, backward);
+                return;
+            }
+            if let (Some((typed, last)), Some((id, _))) = (step.typing, typing) {
+                if typed == id && at.saturating_sub(last) < TYPING_BURST_MS {
+                    // Only the latest text matters going forward, and only
+                    // the text from before the burst going back.
+                    step.forward = vec![forward];
+                    step.typing = typing;
+                    return;
+                }
+            }
+        }
+
+        self.undo.push(Step {
+            forward: vec![forward],
+            backward,
+            typing,
+        });
+    }
+
+    /// Moves the latest step to the redo stack and returns the events that
+    /// revert it.
+    pub fn take_undo(&mut self) -> Option<Vec<Event>> {
+        let mut step = self.undo.pop()?;
+        step.typing = None;
+        if let Some(previous) = self.undo.last_mut() {
+            previous.typing = None;
+        }
+        let backward = step.backward.clone();
+        self.redo.push(step);
+        Some(backward)
+    }
+
+    /// Moves the latest undone step back to the undo stack and returns the
+    /// events that redo it.
+    pub fn take_redo(&mut self) -> Option<Vec<Event>> {
+        let step = self.redo.pop()?;
+        let forward = step.forward.clone();
+        self.undo.push(step);
+        Some(forward)
+    }
+}
+
+impl NodeData {
+    /// Where the node with `id` is: its parent, `None` for children of
+    /// `self`, and its index among the parent's children.
+    fn position(&self, id: NodeId) -> Option<(Option<NodeId>, usize)> {
+        let parent = self.find_parent(id)?;
+        let index = parent.child_index(id)?;
+        let parent = (parent.id != self.id).then_some(parent.id);
+        Some((parent, index))
+    }
+
+    /// Events that revert `event` once it has been applied, computed from the
+    /// tree before it is. Empty if the event does not apply.
+    pub fn inverse(&self, event: &Event) -> Vec<Event> {
+        match *event {
+            Event::Added { id, .. } => vec![Event::Removed { id }],
+            Event::Edited { id, .. } => self
+                .find(id)
+                .map(|node| Event::Edited {
+                    id,
+                    text: node.text.clone(),
+                    from: None,
+                })
+                .into_iter()
+                .collect(),
+            Event::MarkedAsDone { id, .. } | Event::MarkedAsUndone { id } => self
+                .find(id)
+                .map(|node| match node.done {
+                    true => Event::MarkedAsDone {
+                        id,
+                        at: node.done_at,
+                    },
+                    false => Event::MarkedAsUndone { id },
+                })
+                .into_iter()
+                .collect(),
+            Event::Removed { id } => {
+                let mut events = Vec::new();
+                if let (Some((parent, index)), Some(node)) = (self.position(id), self.find(id)) {
+                    node.push_subtree_events(parent, index, &mut events);
+                }
+                events
+            }
+            Event::Moved { id, .. } => self
+                .position(id)
+                .map(|(parent, index)| Event::Moved {
+                    id,
+                    parent,
+                    index,
+                    after: None,
+                })
+                .into_iter()
+                .collect(),
+            Event::Folded { id, .. } => self
+                .find(id)
+                .map(|node| Event::Folded {
+                    id,
+                    open: node.is_open,
+                })
+                .into_iter()
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edit(id: NodeId, text: &str) -> Event {
+        Event::Edited {
+            id,
+            text: text.to_string(),
+            from: None,
+        }
+    }
+
+    #[test]
+    fn test_typing_is_coalesced() {
+        let id = NodeData::next_id();
+        let mut history = History::default();
+        history.record(edit(id, "a"), vec![edit(id, "")], 0);
+        history.record(edit(id, "ab"), vec![edit(id, "a")], 500);
+        history.record(edit(id, "abc"), vec![edit(id, "ab")], 1200);
+        // A pause starts a new step
+        history.record(edit(id, "abcd"), vec![edit(id, "abc")], 3000);
+
+        assert_eq!(history.take_undo(), Some(vec![edit(id, "abc")]));
+        assert_eq!(history.take_undo(), Some(vec![edit(id, "")]));
+        assert_eq!(history.take_undo(), None);
+        assert_eq!(history.take_redo(), Some(vec![edit(id, "abc")]));
+
+        // Typing after undoing does not join the step before it
+        history.record(edit(id, "abcdX"), vec![edit(id, "abcd")], 3100);
+        assert!(!history.can_redo());
+        assert_eq!(history.take_undo(), Some(vec![edit(id, "abcd")]));
+        assert_eq!(history.take_undo(), Some(vec![edit(id, "")]));
+    }
+
+    #[test]
+    fn test_edits_of_other_nodes_are_separate() {
+        let first = NodeData::next_id();
+        let second = NodeData::next_id();
+        let mut history = History::default();
+        history.record(edit(first, "a"), vec![edit(first, "")], 0);
+        history.record(edit(second, "b"), vec![edit(second, "")], 10);
+
+        assert_eq!(history.take_undo(), Some(vec![edit(second, "")]));
+        assert_eq!(history.take_undo(), Some(vec![edit(first, "")]));
+    }
+
+    #[test]
+    fn test_transaction_is_one_step() {
+        let id = NodeData::next_id();
+        let mut history = History::default();
+        history.begin();
+        history.record(
+            Event::Added {
+                id,
+                parent: None,
+                index: 0,
+                after: None,
+                text: String::new(),
+            },
+            vec![Event::Removed { id }],
+            0,
+        );
+        history.record(
+            Event::Folded { id, open: true },
+            vec![Event::Folded { id, open: false }],
+            0,
+        );
+        history.end();
+
+        assert_eq!(
+            history.take_undo(),
+            Some(vec![
+                Event::Folded { id, open: false },
+                Event::Removed { id }
+            ])
+        );
+        assert!(!history.can_undo());
+
+        // Empty transactions leave no step behind
+        history.begin();
+        history.end();
+        assert!(!history.can_undo());
+    }
+
+    #[test]
+    fn test_inverse_of_removed_restores_subtree() {
+        let mut grandchild = NodeData::new(false, "Grandchild", vec![]);
+        grandchild.set_done(true, Some(3));
+        let child = NodeData::new(true, "Child", vec![grandchild]);
+        let sibling = NodeData::new(false, "Sibling", vec![]);
+        let removed = Event::Removed { id: child.id };
+        let mut root = NodeData::new(true, "Root", vec![sibling, child]);
+        let before = root.clone();
+
+        let inverse = root.inverse(&removed);
+        assert!(root.apply(&removed));
+        root.replay(inverse.iter());
+        assert_eq!(root, before);
+    }
+
+    #[test]
+    fn test_inverse_of_moved_restores_position() {
+        let [first, second, third] =
+            ["First", "Second", "Third"].map(|text| NodeData::new(false, text, vec![]));
+        let [first_id, second_id, third_id] = [first.id, second.id, third.id];
+        let mut root = NodeData::new(true, "Root", vec![first, second, third]);
+        let before = root.clone();
+
+        for event in [
+            Event::Moved {
+                id: first_id,
+                parent: None,
+                index: 2,
+                after: None,
+            },
+            Event::Moved {
+                id: third_id,
+                parent: Some(second_id),
+                index: 0,
+                after: None,
+            },
+            Event::Folded {
+                id: second_id,
+                open: true,
+            },
+            Event::MarkedAsDone {
+                id: third_id,
+                at: Some(1),
+            },
+        ] {
+            let inverse = root.inverse(&event);
+            assert!(root.apply(&event));
+            root.replay(inverse.iter());
+            assert_eq!(root, before);
+        }
+
+        let missing = Event::Edited {
+            id: NodeData::next_id(),
+            text: "missing".to_string(),
+            from: None,
+        };
+        assert!(root.inverse(&missing).is_empty());
+    }
+}
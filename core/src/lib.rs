@@ -0,0 +1,21 @@
+//! The outline tree, the events that edit it and the formats it is saved,
+//! imported and exported in, as plain Rust without signals or browser APIs,
+//! so all of it is tested with a plain `cargo test`.
+
+mod error;
+mod event;
+mod history;
+mod markdown;
+mod merge;
+mod node;
+mod opml;
+mod schema;
+mod search;
+
+pub use error::*;
+pub use event::*;
+pub use history::*;
+pub use merge::*;
+pub use node::*;
+pub use schema::*;
+pub use search::*;
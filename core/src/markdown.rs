@@ -0,0 +1,231 @@
+use crate::NodeData;
+
+/// Splits a Markdown list item into the length of its bullet, including the
+/// space after it, and the rest. `None` if `line` does not start with one.
+fn split_bullet(line: &str) -> Option<(usize, &str)> {
+    let digits = line.len() - line.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    let marker = if digits > 0 {
+        let after = &line[digits..];
+        if !(after.starts_with('.') || after.starts_with(')')) {
+            return None;
+        }
+        digits + 1
+    } else if line.starts_with(['-', '*', '+']) {
+        1
+    } else {
+        return None;
+    };
+    match &line[marker..] {
+        "" => Some((marker, "")),
+        rest if rest.starts_with(' ') => Some((marker + 1, &rest[1..])),
+        _ => None,
+    }
+}
+
+/// Splits a task list checkbox off the text of a list item, returning
+/// whether it is checked.
+fn split_checkbox(text: &str) -> (bool, &str) {
+    for (checkbox, done) in [("[ ]", false), ("[x]", true), ("[X]", true)] {
+        if let Some(rest) = text.strip_prefix(checkbox) {
+            if rest.is_empty() || rest.starts_with(' ') {
+                return (done, rest.strip_prefix(' ').unwrap_or(rest));
+            }
+        }
+    }
+    (false, text)
+}
+
+impl NodeData {
+    /// The subtree rooted at this node as a nested Markdown task list, two
+    /// spaces per level. Further lines of a multi-line text are indented
+    /// under their item.
+    pub fn to_markdown(&self) -> String {
+        let mut markdown = String::new();
+        self.push_markdown(0, &mut markdown);
+        markdown
+    }
+
+    fn push_markdown(&self, depth: usize, markdown: &mut String) {
+        let indent = "  ".repeat(depth);
+        let checkbox = if self.done { "[x]" } else { "[ ]" };
+        let mut lines = self.text.lines();
+        let first = lines.next().unwrap_or_default();
+        markdown.push_str(&format!("{}- {} {}\n", indent, checkbox, first));
+        for line in lines {
+            markdown.push_str(&format!("{}      {}\n", indent, line));
+        }
+        for child in &self.children {
+            child.push_markdown(depth + 1, markdown);
+        }
+    }
+
+    /// Parses the items of a nested Markdown list into trees, one per
+    /// top-level item. `-`, `*`, `+` and numbered bullets are accepted, with
+    /// an optional `[ ]` or `[x]` checkbox. Lines that are indented under an
+    /// item without a bullet of their own continue its text; other lines
+    /// become items too. New nodes are open so that nothing is hidden.
+    pub fn from_markdown(markdown: &str) -> Vec<NodeData> {
+        Self::parse_outline(markdown, true)
+    }
+
+    /// Parses lines of plain text into trees, nesting each line under the
+    /// closest line above it that is indented less, with spaces or tabs.
+    /// Bullets and checkboxes are read as in Markdown, but every line is a
+    /// node of its own.
+    pub fn from_indented_text(text: &str) -> Vec<NodeData> {
+        Self::parse_outline(text, false)
+    }
+
+    /// The subtree rooted at this node as indented plain text, one line per
+    /// node and two spaces per level. Line breaks within a text become
+    /// spaces.
+    pub fn to_indented_text(&self) -> String {
+        let mut text = String::new();
+        self.push_indented_text(0, &mut text);
+        text
+    }
+
+    fn push_indented_text(&self, depth: usize, text: &mut String) {
+        let line = self.text.lines().collect::<Vec<_>>().join(" ");
+        text.push_str(&format!("{}{}\n", "  ".repeat(depth), line));
+        for child in &self.children {
+            child.push_indented_text(depth + 1, text);
+        }
+    }
+
+    /// Parses a list of lines indented to show their nesting. With
+    /// `continuations`, lines without a bullet that are indented under an
+    /// item are added to its text.
+    fn parse_outline(outline: &str, continuations: bool) -> Vec<NodeData> {
+        // The items being filled, each with the column of its text, from
+        // the outermost one in.
+        let mut open: Vec<(usize, usize, NodeData)> = Vec::new();
+        let mut roots = Vec::new();
+
+        let close = |open: &mut Vec<(usize, usize, NodeData)>, roots: &mut Vec<NodeData>| {
+            let (_, _, node) = open.pop().expect("an open item");
+            match open.last_mut() {
+                Some((_, _, parent)) => parent.children.push(node),
+                None => roots.push(node),
+            }
+        };
+
+        for line in outline.lines() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let expanded = line.replace('\t', "    ");
+            let indent = expanded.len() - expanded.trim_start().len();
+            let content = expanded.trim_start();
+
+            let Some((marker_len, rest)) = split_bullet(content) else {
+                if let Some((_, text_column, node)) = open.last_mut().filter(|_| continuations) {
+                    if indent >= *text_column {
+                        node.text.push('\n');
+                        node.text.push_str(content.trim_end());
+                        continue;
+                    }
+                }
+                while open.last().is_some_and(|(column, _, _)| *column >= indent) {
+                    close(&mut open, &mut roots);
+                }
+                let node = NodeData::new(true, content.trim_end(), vec![]);
+                open.push((indent, indent, node));
+                continue;
+            };
+
+            while open.last().is_some_and(|(column, _, _)| *column >= indent) {
+                close(&mut open, &mut roots);
+            }
+            let (done, text) = split_checkbox(rest);
+            let mut node = NodeData::new(true, text.trim_end(), vec![]);
+            if done {
+                node.set_done(true, None);
+            }
+            open.push((indent, indent + marker_len, node));
+        }
+        while !open.is_empty() {
+            close(&mut open, &mut roots);
+        }
+        roots
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texts(node: &NodeData) -> Vec<&str> {
+        node.children
+            .iter()
+            .map(|child| child.text.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn test_markdown_roundtrip() {
+        let mut milk = NodeData::new(false, "Milk", vec![]);
+        milk.set_done(true, Some(1));
+        let bread = NodeData::new(false, "Bread\nwholegrain", vec![]);
+        let shopping = NodeData::new(false, "Shopping", vec![milk, bread]);
+        let original = NodeData::new(true, "Today", vec![shopping]);
+
+        let markdown = original.to_markdown();
+        assert_eq!(
+            markdown,
+            "- [ ] Today\n  - [ ] Shopping\n    - [x] Milk\n    - [ ] Bread\n          wholegrain\n"
+        );
+
+        let roundtrip = NodeData::from_markdown(&markdown);
+        assert_eq!(roundtrip.len(), 1);
+        assert_eq!(roundtrip[0].to_markdown(), markdown);
+        let shopping = &roundtrip[0].children[0];
+        assert_eq!(texts(shopping), ["Milk", "Bread\nwholegrain"]);
+        assert!(shopping.children[0].done);
+    }
+
+    #[test]
+    fn test_indented_text() {
+        let text = "Shopping\n\tMilk\n\tBread\n\t\t- [x] sliced\nWork\n  Call Bob\n";
+        let nodes = NodeData::from_indented_text(text);
+        let root = NodeData::new(true, "Root", nodes);
+        assert_eq!(texts(&root), ["Shopping", "Work"]);
+
+        let shopping = &root.children[0];
+        assert_eq!(texts(shopping), ["Milk", "Bread"]);
+        let bread = &shopping.children[1];
+        assert_eq!(texts(bread), ["sliced"]);
+        assert!(bread.children[0].done);
+
+        let mut work = root.children[1].clone();
+        assert_eq!(work.to_indented_text(), "Work\n  Call Bob\n");
+        work.text = "Work\nmonday".to_string();
+        assert_eq!(work.to_indented_text(), "Work monday\n  Call Bob\n");
+    }
+
+    #[test]
+    fn test_from_markdown() {
+        let markdown = "\
+# Heading
+
+* one
+    1. two
+\t3) three
++ [X] four
+-
+-not a bullet
+";
+        let nodes = NodeData::from_markdown(markdown);
+        let root = NodeData::new(true, "Root", nodes);
+        assert_eq!(
+            texts(&root),
+            ["# Heading", "one", "four", "", "-not a bullet"]
+        );
+
+        let one = &root.children[1];
+        assert_eq!(texts(one), ["two", "three"]);
+        assert!(one.is_open);
+        assert!(root.children[2].done);
+        assert!(NodeData::from_markdown("").is_empty());
+    }
+}
@@ -0,0 +1,405 @@
+//! Merging of concurrent events.
+//!
+//! Every replica applies the events of a document in the order of the
+//! server's log, with the local events not accepted yet on top, so replicas
+//! that have seen the same events show the same tree. What is left is to keep
+//! the intent of events that were made without seeing each other: events
+//! record the context they were made in with `NodeData::anchor`, and `apply`
+//! uses it to place them relative to what happened in the meantime.
+//!
+//! - An `Edited` event carries the text it replaced. If the node's text has
+//!   changed since, both changes are kept when they touch different parts of
+//!   the text; where they overlap, the later event wins.
+//! - `Added` and `Moved` events carry the sibling the node was placed after,
+//!   so nodes inserted elsewhere among the siblings do not shift them. If
+//!   that sibling has gone, the index is used.
+//! - A move into the subtree of the moved node does not apply, so moves made
+//!   concurrently cannot create a cycle.
+//!
+//! Events from before this was recorded have no context and are applied as
+//! they were: the text is replaced and the node goes to its index.
+//!
+//! Applying an event again is not harmless: an edit merged into a text that
+//! already contains it and later edits duplicates them. Clients give each
+//! event an id so that the server stores it once, however often it is sent.
+
+use crate::{Event, NodeData, NodeId};
+
+/// The part of `base` that `edited` replaced, as the range `start..end` of
+/// `base` and the characters put in its place.
+fn splice<'a>(base: &[char], edited: &'a [char]) -> (usize, usize, &'a [char]) {
+    let prefix = base.iter().zip(edited).take_while(|(a, b)| a == b).count();
+    let suffix = base[prefix..]
+        .iter()
+        .rev()
+        .zip(edited[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    (
+        prefix,
+        base.len() - suffix,
+        &edited[prefix..edited.len() - suffix],
+    )
+}
+
+/// Merges `incoming`, an edit of `base`, into `current`, another edit of
+/// `base` that was applied first.
+///
+/// Changes to different parts of `base` are both kept, with text inserted at
+/// the same place by `current` first. Where the changes overlap, `incoming`
+/// replaces everything either of them changed.
+pub fn merge_text(base: &str, current: &str, incoming: &str) -> String {
+    if current == incoming || base == incoming {
+        return current.to_string();
+    }
+    let base: Vec<char> = base.chars().collect();
+    let current: Vec<char> = current.chars().collect();
+    let incoming: Vec<char> = incoming.chars().collect();
+    let (a_start, a_end, a_text) = splice(&base, &current);
+    let (b_start, b_end, b_text) = splice(&base, &incoming);
+
+    let parts: Vec<&[char]> = if a_end <= b_start {
+        vec![
+            &base[..a_start],
+            a_text,
+            &base[a_end..b_start],
+            b_text,
+            &base[b_end..],
+        ]
+    } else if b_end <= a_start {
+        vec![
+            &base[..b_start],
+            b_text,
+            &base[b_end..a_start],
+            a_text,
+            &base[a_end..],
+        ]
+    } else {
+        let start = a_start.min(b_start);
+        let end = a_end.max(b_end);
+        let incoming_end = end - (b_end - b_start) + b_text.len();
+        vec![&base[..start], &incoming[start..incoming_end], &base[end..]]
+    };
+    parts.concat().into_iter().collect()
+}
+
+impl NodeData {
+    /// Records the context `event` is made in, in this tree before it is
+    /// applied: the text an edit replaces and the sibling a node is placed
+    /// after. `parent` is taken as in the event log, with `None` for the
+    /// root.
+    pub fn anchor(&self, event: Event) -> Event {
+        match event {
+            Event::Added {
+                id,
+                parent,
+                index,
+                text,
+                ..
+            } => Event::Added {
+                id,
+                parent,
+                index,
+                after: self.sibling_before(parent, index, id),
+                text,
+            },
+            Event::Edited { id, text, .. } => Event::Edited {
+                id,
+                from: self.find(id).map(|node| node.text.clone()),
+                text,
+            },
+            Event::Moved {
+                id, parent, index, ..
+            } => Event::Moved {
+                id,
+                parent,
+                index,
+                after: self.sibling_before(parent, index, id),
+            },
+            event => event,
+        }
+    }
+
+    /// The child of `parent` that a node put at `index` ends up after, not
+    /// counting the node with `id` itself.
+    fn sibling_before(&self, parent: Option<NodeId>, index: usize, id: NodeId) -> Option<NodeId> {
+        let parent = match parent {
+            Some(parent) => self.find(parent)?,
+            None => self,
+        };
+        let siblings: Vec<NodeId> = parent
+            .children
+            .iter()
+            .map(|child| child.id)
+            .filter(|&child| child != id)
+            .collect();
+        let index = index.min(siblings.len());
+        index.checked_sub(1).map(|before| siblings[before])
+    }
+
+    /// Where a child placed after the child with `after`, or at `index` if
+    /// there is no such child, goes among the children once the node with
+    /// `id` has been detached.
+    pub(crate) fn insertion_index(&self, id: NodeId, after: Option<NodeId>, index: usize) -> usize {
+        after
+            .and_then(|after| {
+                self.children
+                    .iter()
+                    .filter(|child| child.id != id)
+                    .position(|child| child.id == after)
+            })
+            .map_or(index, |before| before + 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texts(node: &NodeData) -> Vec<&str> {
+        node.children
+            .iter()
+            .map(|child| child.text.as_str())
+            .collect()
+    }
+
+    /// Applies `event` on a replica the way the event log does, and returns
+    /// it as it is sent to other replicas.
+    fn make(replica: &mut NodeData, event: Event) -> Event {
+        let event = replica.anchor(event);
+        assert!(replica.apply(&event));
+        event
+    }
+
+    /// Every order in which the server can receive the events of two
+    /// replicas, keeping the order of each replica's own events.
+    fn interleavings(a: &[Event], b: &[Event]) -> Vec<Vec<Event>> {
+        match (a.split_first(), b.split_first()) {
+            (None, _) => vec![b.to_vec()],
+            (_, None) => vec![a.to_vec()],
+            (Some((a_first, a_rest)), Some((b_first, b_rest))) => {
+                let mut orders = Vec::new();
+                for mut order in interleavings(a_rest, b) {
+                    order.insert(0, a_first.clone());
+                    orders.push(order);
+                }
+                for mut order in interleavings(a, b_rest) {
+                    order.insert(0, b_first.clone());
+                    orders.push(order);
+                }
+                orders
+            }
+        }
+    }
+
+    /// The tree all replicas end up with after the server received the
+    /// events in `order`.
+    fn merged(base: &NodeData, order: &[Event]) -> NodeData {
+        let mut tree = base.clone();
+        tree.replay(order);
+        tree
+    }
+
+    fn typing(replica: &mut NodeData, id: NodeId, texts: &[&str]) -> Vec<Event> {
+        texts
+            .iter()
+            .map(|text| {
+                make(
+                    replica,
+                    Event::Edited {
+                        id,
+                        text: text.to_string(),
+                        from: None,
+                    },
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_merge_text() {
+        // Edits of different parts are both kept
+        assert_eq!(
+            merge_text("buy milk", "buy oat milk", "buy milk and eggs"),
+            "buy oat milk and eggs"
+        );
+        assert_eq!(
+            merge_text("buy milk", "buy milk and eggs", "buy oat milk"),
+            "buy oat milk and eggs"
+        );
+        assert_eq!(merge_text("one two", "one 2", "1 two"), "1 2");
+        assert_eq!(
+            merge_text("buy milk", "buy oat milk", "buy bread"),
+            "buy oat bread"
+        );
+        // Text inserted at the same place is put in the order it arrived
+        assert_eq!(merge_text("ab", "aXb", "aYb"), "aXYb");
+        // Where the edits overlap, the later one wins
+        assert_eq!(
+            merge_text("buy milk", "buy oat drink", "buy bread"),
+            "buy bread"
+        );
+        assert_eq!(
+            merge_text("one two three", "one 2 three", "one tw0 3"),
+            "one tw0 3"
+        );
+        // The last edit arriving again changes nothing, but an earlier one
+        // does, which is why events are only ever applied once
+        assert_eq!(merge_text("ab", "aXb", "aXb"), "aXb");
+        assert_eq!(merge_text("ab", "aXb", "ab"), "aXb");
+        assert_eq!(merge_text("a", "abc", "ab"), "abcb");
+        assert_eq!(merge_text("", "", "new"), "new");
+        assert_eq!(merge_text("naïve", "naïve café", "Naïve"), "Naïve café");
+    }
+
+    #[test]
+    fn test_concurrent_typing_converges() {
+        let node = NodeData::new(false, "buy milk", vec![]);
+        let id = node.id;
+        let base = NodeData::new(true, "root", vec![node]);
+
+        let mut laptop = base.clone();
+        let laptop_events = typing(
+            &mut laptop,
+            id,
+            &["buy o milk", "buy oa milk", "buy oat milk"],
+        );
+        let mut phone = base.clone();
+        let phone_events = typing(
+            &mut phone,
+            id,
+            &["buy milk ", "buy milk and", "buy milk and eggs"],
+        );
+
+        let orders = interleavings(&laptop_events, &phone_events);
+        assert_eq!(orders.len(), 20);
+        for order in orders {
+            let tree = merged(&base, &order);
+            assert_eq!(tree.find(id).unwrap().text, "buy oat milk and eggs");
+        }
+    }
+
+    #[test]
+    fn test_concurrent_inserts_keep_their_place() {
+        let [a, b, c] = ["a", "b", "c"].map(|text| NodeData::new(false, text, vec![]));
+        let a_id = a.id;
+        let b_id = b.id;
+        let base = NodeData::new(true, "root", vec![a, b, c]);
+
+        // The laptop adds x after a, the phone adds y first and moves b last
+        let mut laptop = base.clone();
+        let laptop_events = vec![make(
+            &mut laptop,
+            Event::Added {
+                id: NodeData::next_id(),
+                parent: None,
+                index: 1,
+                after: None,
+                text: "x".to_string(),
+            },
+        )];
+        let mut phone = base.clone();
+        let phone_events = vec![
+            make(
+                &mut phone,
+                Event::Added {
+                    id: NodeData::next_id(),
+                    parent: None,
+                    index: 0,
+                    after: None,
+                    text: "y".to_string(),
+                },
+            ),
+            make(
+                &mut phone,
+                Event::Moved {
+                    id: b_id,
+                    parent: None,
+                    index: 3,
+                    after: None,
+                },
+            ),
+        ];
+
+        for order in interleavings(&laptop_events, &phone_events) {
+            assert_eq!(texts(&merged(&base, &order)), ["y", "a", "x", "c", "b"]);
+        }
+
+        // Both add a node after a: the one the server got first ends up last
+        let mut phone = base.clone();
+        let phone_events = vec![make(
+            &mut phone,
+            Event::Added {
+                id: NodeData::next_id(),
+                parent: Some(base.id),
+                index: 1,
+                after: None,
+                text: "z".to_string(),
+            }
+            .relative_to(base.id),
+        )];
+        let orders = interleavings(&laptop_events, &phone_events);
+        assert_eq!(texts(&merged(&base, &orders[0])), ["a", "z", "x", "b", "c"]);
+        assert_eq!(texts(&merged(&base, &orders[1])), ["a", "x", "z", "b", "c"]);
+
+        // Without a sibling to go after, the index is used
+        let mut tree = base.clone();
+        tree.apply(&Event::Removed { id: a_id });
+        tree.replay(&laptop_events);
+        assert_eq!(texts(&tree), ["b", "x", "c"]);
+    }
+
+    #[test]
+    fn test_concurrent_moves_do_not_create_cycles() {
+        let x = NodeData::new(false, "x", vec![]);
+        let y = NodeData::new(false, "y", vec![]);
+        let (x_id, y_id) = (x.id, y.id);
+        let base = NodeData::new(true, "root", vec![x, y]);
+
+        let mut laptop = base.clone();
+        let laptop_events = vec![make(
+            &mut laptop,
+            Event::Moved {
+                id: x_id,
+                parent: Some(y_id),
+                index: 0,
+                after: None,
+            },
+        )];
+        let mut phone = base.clone();
+        let phone_events = vec![make(
+            &mut phone,
+            Event::Moved {
+                id: y_id,
+                parent: Some(x_id),
+                index: 0,
+                after: None,
+            },
+        )];
+
+        // The move the server got second would put a node inside itself
+        let orders = interleavings(&laptop_events, &phone_events);
+        let tree = merged(&base, &orders[0]);
+        assert_eq!(texts(&tree), ["y"]);
+        assert_eq!(texts(&tree.children[0]), ["x"]);
+        let tree = merged(&base, &orders[1]);
+        assert_eq!(texts(&tree), ["x"]);
+        assert_eq!(texts(&tree.children[0]), ["y"]);
+    }
+
+    #[test]
+    fn test_edits_of_removed_nodes_are_dropped() {
+        let node = NodeData::new(false, "old", vec![]);
+        let id = node.id;
+        let base = NodeData::new(true, "root", vec![node]);
+
+        let mut laptop = base.clone();
+        let laptop_events = typing(&mut laptop, id, &["new"]);
+        let mut phone = base.clone();
+        let phone_events = vec![make(&mut phone, Event::Removed { id })];
+
+        for order in interleavings(&laptop_events, &phone_events) {
+            assert!(merged(&base, &order).children.is_empty());
+        }
+    }
+}
@@ -0,0 +1,699 @@
+use std::collections::HashMap;
+
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+use crate::NodeError;
+
+/// Globally unique node identifier, shared with the `events` table.
+pub type NodeId = Uuid;
+
+/// Where a dragged node is dropped relative to the node under the pointer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DropPosition {
+    Before,
+    After,
+    /// As the first child.
+    Inside,
+}
+
+/// A node and its subtree as plain data. This is the tree that edits are
+/// applied to and that is saved; the app shows it through reactive signals.
+#[derive(Clone, Debug, PartialEq)]
+pub struct NodeData {
+    pub id: NodeId,
+    pub is_open: bool,
+    pub text: String,
+    pub done: bool,
+    /// When the node was marked as done, in milliseconds since the Unix epoch.
+    /// `None` if it is not done or the time is unknown.
+    pub done_at: Option<u64>,
+    pub children: Vec<NodeData>,
+}
+
+impl NodeData {
+    pub fn new(is_open: bool, text: &str, children: Vec<NodeData>) -> Self {
+        Self::with_id(Self::next_id(), is_open, text, children)
+    }
+
+    pub fn with_id(id: NodeId, is_open: bool, text: &str, children: Vec<NodeData>) -> Self {
+        Self {
+            id,
+            is_open,
+            text: text.to_string(),
+            done: false,
+            done_at: None,
+            children,
+        }
+    }
+
+    /// Allocates an id for a node that is about to be created.
+    pub fn next_id() -> NodeId {
+        Uuid::new_v4()
+    }
+
+    /// Parses a tree as written by `to_json`. Errors point at the first
+    /// value that does not fit, e.g. `$.children[2].text`.
+    pub fn from_json(value: &Value) -> Result<Self, NodeError> {
+        Self::from_json_at(value, "$")
+    }
+
+    fn from_json_at(value: &Value, path: &str) -> Result<Self, NodeError> {
+        if !value.is_object() {
+            return Err(NodeError::schema(path, "expected an object"));
+        }
+        let field = |name: &str, expected: &str| {
+            NodeError::schema(
+                &format!("{}.{}", path, name),
+                format!("expected {}", expected),
+            )
+        };
+
+        let id = value["id"]
+            .as_str()
+            .and_then(|id| Uuid::parse_str(id).ok())
+            .ok_or_else(|| field("id", "a UUID string"))?;
+        let is_open = value["is_open"]
+            .as_bool()
+            .ok_or_else(|| field("is_open", "a boolean"))?;
+        let text = value["text"]
+            .as_str()
+            .ok_or_else(|| field("text", "a string"))?
+            .to_string();
+        // Trees saved before completion state existed have neither field.
+        let done = match &value["done"] {
+            Value::Null => false,
+            done => done.as_bool().ok_or_else(|| field("done", "a boolean"))?,
+        };
+        let done_at = match &value["done_at"] {
+            Value::Null => None,
+            done_at => Some(
+                done_at
+                    .as_u64()
+                    .ok_or_else(|| field("done_at", "a timestamp"))?,
+            ),
+        };
+
+        let children = value["children"]
+            .as_array()
+            .ok_or_else(|| field("children", "an array"))?
+            .iter()
+            .enumerate()
+            .map(|(index, child)| {
+                NodeData::from_json_at(child, &format!("{}.children[{}]", path, index))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self {
+            id,
+            is_open,
+            text,
+            done,
+            done_at,
+            children,
+        })
+    }
+
+    pub fn to_json(&self) -> Value {
+        let children: Vec<Value> = self.children.iter().map(NodeData::to_json).collect();
+
+        json!({
+            "id": self.id.to_string(),
+            "is_open": self.is_open,
+            "text": self.text,
+            "done": self.done,
+            "done_at": self.done_at,
+            "children": children
+        })
+    }
+
+    /// Finds the node with `id` in the subtree rooted at `self`, including `self`.
+    pub fn find(&self, id: NodeId) -> Option<&NodeData> {
+        if self.id == id {
+            return Some(self);
+        }
+        self.children.iter().find_map(|child| child.find(id))
+    }
+
+    pub fn find_mut(&mut self, id: NodeId) -> Option<&mut NodeData> {
+        if self.id == id {
+            return Some(self);
+        }
+        self.children
+            .iter_mut()
+            .find_map(|child| child.find_mut(id))
+    }
+
+    /// Finds the parent of the node with `id` in the subtree rooted at `self`.
+    pub fn find_parent(&self, id: NodeId) -> Option<&NodeData> {
+        if self.children.iter().any(|child| child.id == id) {
+            return Some(self);
+        }
+        self.children.iter().find_map(|child| child.find_parent(id))
+    }
+
+    pub fn find_parent_mut(&mut self, id: NodeId) -> Option<&mut NodeData> {
+        if self.children.iter().any(|child| child.id == id) {
+            return Some(self);
+        }
+        self.children
+            .iter_mut()
+            .find_map(|child| child.find_parent_mut(id))
+    }
+
+    /// The nodes from `self` down to the parent of the node with `id`, or
+    /// `None` if it is not in the subtree rooted at `self`.
+    pub fn ancestors(&self, id: NodeId) -> Option<Vec<&NodeData>> {
+        if self.id == id {
+            return Some(Vec::new());
+        }
+        self.children.iter().find_map(|child| {
+            let mut ancestors = child.ancestors(id)?;
+            ancestors.insert(0, self);
+            Some(ancestors)
+        })
+    }
+
+    /// Marks the node as done at `at`, or clears its completion state.
+    pub fn set_done(&mut self, done: bool, at: Option<u64>) {
+        self.done = done;
+        self.done_at = if done { at } else { None };
+    }
+
+    pub fn prepend_child(&mut self, child: NodeData) {
+        self.children.insert(0, child);
+    }
+
+    /// Inserts `child` at `index`, or appends it if `index` is past the end.
+    pub fn insert_child_at(&mut self, index: usize, child: NodeData) {
+        let index = index.min(self.children.len());
+        self.children.insert(index, child);
+    }
+
+    /// Position of the direct child with `id`.
+    pub fn child_index(&self, id: NodeId) -> Option<usize> {
+        self.children.iter().position(|child| child.id == id)
+    }
+
+    /// Detaches the direct child with `id` and returns it.
+    pub fn remove_child(&mut self, id: NodeId) -> Option<NodeData> {
+        let index = self.child_index(id)?;
+        Some(self.children.remove(index))
+    }
+
+    /// Moves the node with `id`, including its subtree, to position `index`
+    /// among the children of `parent`. The index is taken after the node has
+    /// been detached from its old position.
+    ///
+    /// Returns `false` without changing anything if either node is not in this
+    /// tree, if `id` is the root, or if `parent` lies inside the moved subtree.
+    pub fn move_child(&mut self, id: NodeId, parent: NodeId, index: usize) -> bool {
+        let Some(node) = self.find(id) else {
+            return false;
+        };
+        if node.find(parent).is_some() || self.find(parent).is_none() {
+            return false;
+        }
+        let Some(node) = self
+            .find_parent_mut(id)
+            .and_then(|old_parent| old_parent.remove_child(id))
+        else {
+            return false;
+        };
+        if let Some(new_parent) = self.find_mut(parent) {
+            new_parent.insert_child_at(index, node);
+        }
+        true
+    }
+
+    /// Where moving the node with `id` by `offset` places among its siblings
+    /// would put it, or `None` if that would leave its parent.
+    pub fn reorder_target(&self, id: NodeId, offset: isize) -> Option<(NodeId, usize)> {
+        let parent = self.find_parent(id)?;
+        let index = parent.child_index(id)?.checked_add_signed(offset)?;
+        if index >= parent.children.len() {
+            return None;
+        }
+        Some((parent.id, index))
+    }
+
+    /// Where `indent` would move the node with `id`: to the end of its
+    /// previous sibling's children.
+    pub fn indent_target(&self, id: NodeId) -> Option<(NodeId, usize)> {
+        let parent = self.find_parent(id)?;
+        let index = parent.child_index(id)?;
+        let previous = parent.children.get(index.checked_sub(1)?)?;
+        Some((previous.id, previous.children.len()))
+    }
+
+    /// Where `outdent` would move the node with `id`: right after its parent
+    /// among its grandparent's children.
+    pub fn outdent_target(&self, id: NodeId) -> Option<(NodeId, usize)> {
+        let parent = self.find_parent(id)?;
+        let grandparent = self.find_parent(parent.id)?;
+        let index = grandparent.child_index(parent.id)?;
+        Some((grandparent.id, index + 1))
+    }
+
+    /// Where dropping the node with `id` at `position` relative to `target`
+    /// would move it, or `None` if the drop is not allowed: the root cannot
+    /// be moved or get siblings, and a node cannot be dropped onto itself or
+    /// into its own subtree.
+    pub fn drop_target(
+        &self,
+        id: NodeId,
+        target: NodeId,
+        position: DropPosition,
+    ) -> Option<(NodeId, usize)> {
+        let node = self.find(id)?;
+        let old_parent = self.find_parent(id)?;
+        if node.find(target).is_some() {
+            return None;
+        }
+
+        if position == DropPosition::Inside {
+            self.find(target)?;
+            return Some((target, 0));
+        }
+
+        let parent = self.find_parent(target)?;
+        let mut index = parent.child_index(target)?;
+        if position == DropPosition::After {
+            index += 1;
+        }
+        // `move_child` counts positions after the node has been detached.
+        if old_parent.id == parent.id && parent.child_index(id)? < index {
+            index -= 1;
+        }
+        Some((parent.id, index))
+    }
+
+    /// Takes over the fold state of the nodes that also exist in `other`.
+    /// Fold state is local to a view, so a tree that arrives from elsewhere
+    /// should not open or close what is on screen.
+    pub fn keep_fold_state(&mut self, other: &NodeData) {
+        fn collect(node: &NodeData, open: &mut HashMap<NodeId, bool>) {
+            open.insert(node.id, node.is_open);
+            node.children.iter().for_each(|child| collect(child, open));
+        }
+        fn update(node: &mut NodeData, open: &HashMap<NodeId, bool>) {
+            if let Some(&is_open) = open.get(&node.id) {
+                node.is_open = is_open;
+            }
+            node.children
+                .iter_mut()
+                .for_each(|child| update(child, open));
+        }
+
+        let mut open = HashMap::new();
+        collect(other, &mut open);
+        update(self, &open);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texts(node: &NodeData) -> Vec<&str> {
+        node.children
+            .iter()
+            .map(|child| child.text.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn test_prepend_child() {
+        let child1 = NodeData::new(false, "Child 1", vec![]);
+        let child2 = NodeData::new(false, "Child 2", vec![]);
+        let mut node = NodeData::new(true, "Parent", vec![child1]);
+        assert_eq!(texts(&node), ["Child 1"]);
+
+        node.prepend_child(child2);
+        assert_eq!(texts(&node), ["Child 2", "Child 1"]);
+    }
+
+    #[test]
+    fn test_remove_child() {
+        let child1 = NodeData::new(false, "Child 1", vec![]);
+        let child2 = NodeData::new(false, "Child 2", vec![]);
+        let (child1_id, child2_id) = (child1.id, child2.id);
+        let mut node = NodeData::new(true, "Parent", vec![child1, child2]);
+
+        let removed = node.remove_child(child1_id).unwrap();
+        assert_eq!(removed.text, "Child 1");
+        assert_eq!(node.children.len(), 1);
+        assert_eq!(node.children[0].id, child2_id);
+
+        // Try to remove non-existent child
+        assert_eq!(node.remove_child(NodeData::next_id()), None);
+        assert_eq!(node.children.len(), 1);
+    }
+
+    #[test]
+    fn test_insert_child_at() {
+        let mut node = NodeData::new(true, "Parent", vec![]);
+        node.insert_child_at(0, NodeData::new(false, "B", vec![]));
+        node.insert_child_at(0, NodeData::new(false, "A", vec![]));
+        node.insert_child_at(99, NodeData::new(false, "D", vec![]));
+        node.insert_child_at(2, NodeData::new(false, "C", vec![]));
+
+        assert_eq!(texts(&node), ["A", "B", "C", "D"]);
+    }
+
+    #[test]
+    fn test_ancestors() {
+        let c = NodeData::new(false, "C", vec![]);
+        let b = NodeData::new(false, "B", vec![c.clone()]);
+        let a = NodeData::new(false, "A", vec![]);
+        let root = NodeData::new(true, "Root", vec![a.clone(), b.clone()]);
+
+        let ids = |nodes: Vec<&NodeData>| nodes.iter().map(|node| node.id).collect::<Vec<_>>();
+        assert_eq!(root.ancestors(c.id).map(ids), Some(vec![root.id, b.id]));
+        assert_eq!(root.ancestors(a.id).map(ids), Some(vec![root.id]));
+        assert_eq!(root.ancestors(root.id).map(ids), Some(vec![]));
+        assert_eq!(root.ancestors(NodeData::next_id()).map(ids), None);
+    }
+
+    /// A root with children `A`, `B` and `C`, and their ids.
+    fn abc() -> (NodeData, [NodeId; 3]) {
+        let children: Vec<NodeData> = ["A", "B", "C"]
+            .iter()
+            .map(|text| NodeData::new(false, text, vec![]))
+            .collect();
+        let ids = [children[0].id, children[1].id, children[2].id];
+        (NodeData::new(true, "Root", children), ids)
+    }
+
+    #[test]
+    fn test_move_child() {
+        let (mut root, [a, b, c]) = abc();
+
+        assert!(root.move_child(a, b, 0));
+        assert_eq!(texts(&root), ["B", "C"]);
+        assert_eq!(texts(root.find(b).unwrap()), ["A"]);
+
+        // Reorder within the same parent
+        assert!(root.move_child(c, root.id, 0));
+        assert_eq!(texts(&root), ["C", "B"]);
+
+        // Cannot move a node into its own subtree, or move the root
+        assert!(!root.move_child(b, a, 0));
+        assert!(!root.move_child(b, b, 0));
+        assert!(!root.move_child(root.id, c, 0));
+        assert!(!root.move_child(NodeData::next_id(), root.id, 0));
+        assert_eq!(texts(&root), ["C", "B"]);
+        assert_eq!(texts(root.find(b).unwrap()), ["A"]);
+    }
+
+    #[test]
+    fn test_reorder_target() {
+        let (mut root, [a, b, c]) = abc();
+
+        assert_eq!(root.reorder_target(a, -1), None);
+        assert_eq!(root.reorder_target(c, 1), None);
+        assert_eq!(root.reorder_target(root.id, 1), None);
+        assert_eq!(root.reorder_target(b, -1), Some((root.id, 0)));
+        assert_eq!(root.reorder_target(b, 1), Some((root.id, 2)));
+
+        let (parent, index) = root.reorder_target(a, 1).unwrap();
+        assert!(root.move_child(a, parent, index));
+        assert_eq!(texts(&root), ["B", "A", "C"]);
+
+        let (parent, index) = root.reorder_target(c, -2).unwrap();
+        assert!(root.move_child(c, parent, index));
+        assert_eq!(texts(&root), ["C", "B", "A"]);
+    }
+
+    #[test]
+    fn test_drop_target() {
+        let a1 = NodeData::new(false, "A1", vec![]);
+        let a1_id = a1.id;
+        let (mut root, [a, b, c]) = abc();
+        root.find_mut(a).unwrap().insert_child_at(0, a1);
+
+        let mut drop = |id: NodeId, target: NodeId, position| {
+            let (parent, index) = root.drop_target(id, target, position).unwrap();
+            assert!(root.move_child(id, parent, index));
+            root.clone()
+        };
+
+        assert_eq!(texts(&drop(c, a, DropPosition::Before)), ["C", "A", "B"]);
+        assert_eq!(texts(&drop(c, b, DropPosition::After)), ["A", "B", "C"]);
+        assert_eq!(texts(&drop(a, b, DropPosition::Before)), ["A", "B", "C"]);
+
+        let root = drop(c, a1_id, DropPosition::After);
+        assert_eq!(texts(&root), ["A", "B"]);
+        assert_eq!(texts(root.find(a).unwrap()), ["A1", "C"]);
+
+        let root = drop(a1_id, b, DropPosition::Inside);
+        assert_eq!(texts(root.find(a).unwrap()), ["C"]);
+        assert_eq!(texts(root.find(b).unwrap()), ["A1"]);
+    }
+
+    #[test]
+    fn test_drop_target_rejects_cycles_and_root() {
+        let a1 = NodeData::next_id();
+        let (mut root, [a, b, _]) = abc();
+        root.find_mut(a)
+            .unwrap()
+            .insert_child_at(0, NodeData::with_id(a1, false, "A1", vec![]));
+
+        for position in [
+            DropPosition::Before,
+            DropPosition::After,
+            DropPosition::Inside,
+        ] {
+            assert_eq!(root.drop_target(a, a, position), None);
+            assert_eq!(root.drop_target(a, a1, position), None);
+            assert_eq!(root.drop_target(root.id, b, position), None);
+            assert_eq!(root.drop_target(b, NodeData::next_id(), position), None);
+        }
+        assert_eq!(root.drop_target(b, root.id, DropPosition::Before), None);
+        assert_eq!(root.drop_target(b, root.id, DropPosition::After), None);
+        assert_eq!(
+            root.drop_target(b, root.id, DropPosition::Inside),
+            Some((root.id, 0))
+        );
+    }
+
+    #[test]
+    fn test_indent_and_outdent_targets() {
+        let (mut root, [a, b, c]) = abc();
+
+        // The first child has no previous sibling to indent under
+        assert_eq!(root.indent_target(a), None);
+        assert_eq!(root.indent_target(b), Some((a, 0)));
+
+        for id in [b, c] {
+            let (parent, index) = root.indent_target(id).unwrap();
+            assert!(root.move_child(id, parent, index));
+        }
+        assert_eq!(texts(&root), ["A"]);
+        assert_eq!(texts(root.find(a).unwrap()), ["B", "C"]);
+
+        assert_eq!(root.outdent_target(b), Some((root.id, 1)));
+        let (parent, index) = root.outdent_target(b).unwrap();
+        assert!(root.move_child(b, parent, index));
+        assert_eq!(texts(&root), ["A", "B"]);
+        assert_eq!(texts(root.find(a).unwrap()), ["C"]);
+
+        // Children of the root cannot be outdented
+        assert_eq!(root.outdent_target(a), None);
+        assert_eq!(root.outdent_target(root.id), None);
+    }
+
+    #[test]
+    fn test_keep_fold_state() {
+        let (local, [a, b, _]) = abc();
+        let mut remote = local.clone();
+        remote.find_mut(a).unwrap().is_open = true;
+        remote.find_mut(b).unwrap().text = "B edited".to_string();
+        let added = NodeData::new(true, "Added", vec![]);
+        remote.insert_child_at(0, added);
+
+        remote.keep_fold_state(&local);
+        assert!(!remote.find(a).unwrap().is_open);
+        // Nodes that are new keep their own fold state
+        assert!(remote.children[0].is_open);
+        assert_eq!(texts(&remote), ["Added", "A", "B edited", "C"]);
+    }
+
+    #[test]
+    fn test_to_json() {
+        let grandchild = NodeData::new(false, "Grandchild", vec![]);
+        let child = NodeData::new(true, "Child", vec![grandchild]);
+        let parent = NodeData::new(true, "Parent", vec![child]);
+
+        let json = parent.to_json();
+
+        // Verify top-level properties
+        assert_eq!(json["text"], "Parent");
+        assert_eq!(json["is_open"], true);
+        assert!(Uuid::parse_str(json["id"].as_str().unwrap()).is_ok());
+
+        // Verify child structure
+        let children = &json["children"];
+        assert!(children.is_array());
+        assert_eq!(children.as_array().unwrap().len(), 1);
+
+        let first_child = &children[0];
+        assert_eq!(first_child["text"], "Child");
+        assert_eq!(first_child["is_open"], true);
+
+        // Verify grandchild structure
+        let first_grandchild = &first_child["children"][0];
+        assert_eq!(first_grandchild["text"], "Grandchild");
+        assert_eq!(first_grandchild["is_open"], false);
+        assert!(first_grandchild["children"].as_array().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_from_json() {
+        let json = json!({
+            "id": "8722655e-f231-11ef-8932-1f1e2ee24d96",
+            "is_open": true,
+            "text": "Parent",
+            "children": [
+                {
+                    "id": "1302f702-f23a-11ef-a65c-63c495723c09",
+                    "is_open": false,
+                    "text": "Child",
+                    "children": []
+                }
+            ]
+        });
+
+        let node = NodeData::from_json(&json).unwrap();
+        assert_eq!(node.id.to_string(), "8722655e-f231-11ef-8932-1f1e2ee24d96");
+        assert!(node.is_open);
+        assert_eq!(node.text, "Parent");
+
+        assert_eq!(node.children.len(), 1);
+        let child = &node.children[0];
+        assert_eq!(child.id.to_string(), "1302f702-f23a-11ef-a65c-63c495723c09");
+        assert!(!child.is_open);
+        assert_eq!(child.text, "Child");
+        assert!(child.children.is_empty());
+    }
+
+    #[test]
+    fn test_done_json_roundtrip() {
+        let mut child = NodeData::new(false, "Child", vec![]);
+        child.set_done(true, Some(1_700_000_000_000));
+        let parent = NodeData::new(true, "Parent", vec![child]);
+
+        let json = parent.to_json();
+        assert_eq!(json["done"], false);
+        assert!(json["done_at"].is_null());
+        assert_eq!(json["children"][0]["done"], true);
+        assert_eq!(json["children"][0]["done_at"], 1_700_000_000_000u64);
+
+        let mut roundtrip = NodeData::from_json(&json).unwrap();
+        assert_eq!(roundtrip, parent);
+
+        let roundtrip_child = &mut roundtrip.children[0];
+        roundtrip_child.set_done(false, Some(1));
+        assert!(!roundtrip_child.done);
+        assert_eq!(roundtrip_child.done_at, None);
+    }
+
+    #[test]
+    fn test_from_json_without_done_fields() {
+        let json = json!({
+            "id": "8722655e-f231-11ef-8932-1f1e2ee24d96",
+            "is_open": true,
+            "text": "Saved before done existed",
+            "children": []
+        });
+
+        let node = NodeData::from_json(&json).unwrap();
+        assert!(!node.done);
+        assert_eq!(node.done_at, None);
+    }
+
+    #[test]
+    fn test_from_json_rejects_invalid_id() {
+        let json = json!({
+            "id": 123,
+            "is_open": true,
+            "text": "Parent",
+            "children": []
+        });
+        assert!(NodeData::from_json(&json).is_err());
+
+        let json = json!({
+            "id": "not-a-uuid",
+            "is_open": true,
+            "text": "Parent",
+            "children": []
+        });
+        assert_eq!(
+            NodeData::from_json(&json).err(),
+            Some(NodeError::schema("$.id", "expected a UUID string"))
+        );
+    }
+
+    #[test]
+    fn test_from_json_reports_path() {
+        let child = |text: Value| {
+            json!({
+                "id": NodeData::next_id().to_string(),
+                "is_open": false,
+                "text": text,
+                "children": []
+            })
+        };
+        let json = json!({
+            "id": NodeData::next_id().to_string(),
+            "is_open": true,
+            "text": "Parent",
+            "children": [
+                child(json!("Fine")),
+                {
+                    "id": NodeData::next_id().to_string(),
+                    "is_open": true,
+                    "text": "Nested",
+                    "children": [child(json!("Fine")), child(json!(42))]
+                }
+            ]
+        });
+
+        // Malformed children are reported rather than dropped
+        assert_eq!(
+            NodeData::from_json(&json).err(),
+            Some(NodeError::schema(
+                "$.children[1].children[1].text",
+                "expected a string"
+            ))
+        );
+        assert_eq!(
+            NodeData::from_json(&json!([])).err(),
+            Some(NodeError::schema("$", "expected an object"))
+        );
+
+        let mut json = child(json!("Done"));
+        json["done"] = json!("yes");
+        assert_eq!(
+            NodeData::from_json(&json).err().map(|err| err.to_string()),
+            Some("Invalid tree at $.done: expected a boolean".to_string())
+        );
+    }
+
+    #[test]
+    fn test_new_nodes_have_distinct_ids() {
+        let first = NodeData::new(false, "First", vec![]);
+        let second = NodeData::new(false, "Second", vec![]);
+        assert_ne!(first.id, second.id);
+    }
+
+    #[test]
+    fn test_json_roundtrip() {
+        let grandchild = NodeData::new(false, "Grandchild", vec![]);
+        let child = NodeData::new(true, "Child", vec![grandchild]);
+        let original = NodeData::new(true, "Parent", vec![child]);
+
+        let roundtrip = NodeData::from_json(&original.to_json()).unwrap();
+        assert_eq!(roundtrip, original);
+    }
+}
@@ -0,0 +1,164 @@
+use crate::{NodeData, NodeError};
+
+/// Escapes `text` for use in XML content and attribute values. Line breaks
+/// are escaped too, since attribute values would lose them otherwise.
+fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\n' => escaped.push_str("&#10;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// The `outline` elements directly inside `parent`.
+fn outlines<'a, 'input>(
+    parent: roxmltree::Node<'a, 'input>,
+) -> impl Iterator<Item = roxmltree::Node<'a, 'input>> {
+    parent
+        .children()
+        .filter(|child| child.has_tag_name("outline"))
+}
+
+impl NodeData {
+    /// The subtree rooted at this node as an OPML document with a single
+    /// top-level outline. `is_open` is kept in an `_open` attribute and the
+    /// completion state in `_complete`, as Workflowy does.
+    pub fn to_opml(&self) -> String {
+        let mut opml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        opml.push_str("<opml version=\"2.0\">\n");
+        opml.push_str(&format!(
+            "  <head>\n    <title>{}</title>\n  </head>\n",
+            escape_xml(&self.text)
+        ));
+        opml.push_str("  <body>\n");
+        self.push_opml(2, &mut opml);
+        opml.push_str("  </body>\n</opml>\n");
+        opml
+    }
+
+    fn push_opml(&self, depth: usize, opml: &mut String) {
+        let indent = "  ".repeat(depth);
+        opml.push_str(&format!(
+            "{}<outline text=\"{}\" _open=\"{}\"",
+            indent,
+            escape_xml(&self.text),
+            self.is_open
+        ));
+        if self.done {
+            opml.push_str(" _complete=\"true\"");
+        }
+        if self.children.is_empty() {
+            opml.push_str("/>\n");
+            return;
+        }
+        opml.push_str(">\n");
+        for child in &self.children {
+            child.push_opml(depth + 1, opml);
+        }
+        opml.push_str(&format!("{}</outline>\n", indent));
+    }
+
+    /// Parses the outlines in the body of an OPML document into trees, one
+    /// per top-level outline. Outlines without an `_open` attribute, as
+    /// written by other outliners, are open.
+    pub fn from_opml(opml: &str) -> Result<Vec<NodeData>, NodeError> {
+        let document = roxmltree::Document::parse(opml)?;
+        let body = document
+            .root_element()
+            .children()
+            .find(|element| element.has_tag_name("body"))
+            .ok_or_else(|| NodeError::schema("/opml", "expected a body element"))?;
+        Ok(outlines(body).map(NodeData::from_outline).collect())
+    }
+
+    fn from_outline(outline: roxmltree::Node) -> NodeData {
+        let is_open = outline.attribute("_open") != Some("false");
+        let text = outline.attribute("text").unwrap_or_default();
+        let children = outlines(outline).map(NodeData::from_outline).collect();
+        let mut node = NodeData::new(is_open, text, children);
+        if outline.attribute("_complete") == Some("true") {
+            node.set_done(true, None);
+        }
+        node
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texts(node: &NodeData) -> Vec<&str> {
+        node.children
+            .iter()
+            .map(|child| child.text.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn test_opml_roundtrip() {
+        let mut milk = NodeData::new(false, "Milk & <eggs>", vec![]);
+        milk.set_done(true, Some(1));
+        let bread = NodeData::new(false, "Bread \"wholegrain\"\nsliced", vec![]);
+        let shopping = NodeData::new(false, "Shopping", vec![milk, bread]);
+        let original = NodeData::new(true, "Today", vec![shopping]);
+
+        let opml = original.to_opml();
+        assert!(opml.contains("<title>Today</title>"));
+        assert!(opml.contains(
+            "<outline text=\"Milk &amp; &lt;eggs&gt;\" _open=\"false\" _complete=\"true\"/>"
+        ));
+
+        let roundtrip = NodeData::from_opml(&opml).unwrap();
+        assert_eq!(roundtrip.len(), 1);
+        assert_eq!(roundtrip[0].to_opml(), opml);
+        assert_eq!(roundtrip[0].text, "Today");
+        assert!(roundtrip[0].is_open);
+
+        let shopping = &roundtrip[0].children[0];
+        assert!(!shopping.is_open);
+        assert_eq!(
+            texts(shopping),
+            ["Milk & <eggs>", "Bread \"wholegrain\"\nsliced"]
+        );
+        assert!(shopping.children[0].done);
+    }
+
+    #[test]
+    fn test_from_opml() {
+        // As written by other outliners: no fold state, and other attributes
+        let opml = r#"<?xml version="1.0"?>
+<opml version="2.0">
+  <head><title>Export</title></head>
+  <body>
+    <outline text="One" _note="ignored">
+      <outline text="Two"/>
+    </outline>
+    <outline text="Three" _complete="true"/>
+    <outline/>
+  </body>
+</opml>"#;
+        let root = NodeData::new(true, "Root", NodeData::from_opml(opml).unwrap());
+        assert_eq!(texts(&root), ["One", "Three", ""]);
+
+        let one = &root.children[0];
+        assert!(one.is_open);
+        assert_eq!(texts(one), ["Two"]);
+        assert!(root.children[1].done);
+
+        assert_eq!(
+            NodeData::from_opml("<opml><head/></opml>"),
+            Err(NodeError::schema("/opml", "expected a body element"))
+        );
+        assert!(matches!(
+            NodeData::from_opml("not xml"),
+            Err(NodeError::XmlSyntax(_))
+        ));
+    }
+}
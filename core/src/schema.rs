@@ -0,0 +1,180 @@
+use serde_json::{json, Value};
+
+use crate::{NodeData, NodeError};
+
+/// Version of the documents written by `NodeData::to_document`.
+///
+/// 0. The bare tree of `NodeData::to_json`, before completion state existed.
+///    The first of these have numeric ids instead of UUIDs.
+/// 1. The bare tree with `done` and `done_at` on every node.
+/// 2. The tree under `tree` in an envelope with its `version`.
+pub const SCHEMA_VERSION: u64 = 2;
+
+/// Upgrades a document of version `index` to the next version.
+type Migration = fn(Value) -> Result<Value, NodeError>;
+
+const MIGRATIONS: [Migration; SCHEMA_VERSION as usize] =
+    [add_uuids_and_done_fields, wrap_in_envelope];
+
+/// 0 to 1: gives nodes with a numeric id a fresh UUID, and marks every node
+/// as not done.
+///
+/// Numeric ids started over at 1 on every page load, so the same number can
+/// stand for several nodes of a tree. Each of them gets its own UUID.
+fn add_uuids_and_done_fields(mut tree: Value) -> Result<Value, NodeError> {
+    fn visit(node: &mut Value) {
+        let Some(object) = node.as_object_mut() else {
+            return;
+        };
+        if object.get("id").is_some_and(Value::is_u64) {
+            object.insert("id".to_string(), json!(NodeData::next_id().to_string()));
+        }
+        object.entry("done").or_insert(json!(false));
+        object.entry("done_at").or_insert(Value::Null);
+        if let Some(children) = object.get_mut("children").and_then(Value::as_array_mut) {
+            children.iter_mut().for_each(visit);
+        }
+    }
+    visit(&mut tree);
+    Ok(tree)
+}
+
+/// 1 to 2: puts the tree into an envelope.
+fn wrap_in_envelope(tree: Value) -> Result<Value, NodeError> {
+    Ok(json!({ "version": 2, "tree": tree }))
+}
+
+/// The version of `document`. Documents before version 2 have no envelope,
+/// and those before version 1 no completion state.
+pub fn schema_version(document: &Value) -> Result<u64, NodeError> {
+    match &document["version"] {
+        Value::Null if document.get("done").is_some() => Ok(1),
+        Value::Null => Ok(0),
+        version => version
+            .as_u64()
+            .ok_or_else(|| NodeError::schema("$.version", "expected a number")),
+    }
+}
+
+/// Runs the migrations that bring `document` up to `SCHEMA_VERSION`.
+pub fn upgrade(mut document: Value) -> Result<Value, NodeError> {
+    let version = schema_version(&document)?;
+    if version > SCHEMA_VERSION {
+        return Err(NodeError::UnsupportedVersion(version));
+    }
+    for migration in &MIGRATIONS[version as usize..] {
+        document = migration(document)?;
+    }
+    Ok(document)
+}
+
+impl NodeData {
+    /// The tree as a document of the current `SCHEMA_VERSION`, the form it
+    /// is saved in.
+    pub fn to_document(&self) -> Value {
+        json!({ "version": SCHEMA_VERSION, "tree": self.to_json() })
+    }
+
+    /// Reads a saved document of any version, upgrading it first.
+    pub fn from_document(document: Value) -> Result<Self, NodeError> {
+        let document = upgrade(document)?;
+        Self::from_json(&document["tree"]).map_err(|err| match err {
+            NodeError::Schema { path, message } => NodeError::Schema {
+                path: path.replacen('$', "$.tree", 1),
+                message,
+            },
+            err => err,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIXTURES: [&str; 3] = [
+        include_str!("fixtures/v0.json"),
+        include_str!("fixtures/v1.json"),
+        include_str!("fixtures/v2.json"),
+    ];
+
+    fn fixture(version: usize) -> Value {
+        serde_json::from_str(FIXTURES[version]).unwrap()
+    }
+
+    #[test]
+    fn test_fixture_versions() {
+        assert_eq!(FIXTURES.len() as u64, SCHEMA_VERSION + 1);
+        for (version, _) in FIXTURES.iter().enumerate() {
+            assert_eq!(schema_version(&fixture(version)), Ok(version as u64));
+        }
+    }
+
+    #[test]
+    fn test_load_every_version() {
+        for version in 0..FIXTURES.len() {
+            let node = NodeData::from_document(fixture(version)).unwrap();
+            assert_eq!(node.text, "Groceries");
+            assert_eq!(node.children.len(), 2);
+            let milk = &node.children[0];
+            assert_eq!(milk.text, "Milk");
+            assert!(!milk.is_open);
+            // Version 0 had no completion state to carry over
+            assert_eq!(milk.done, version > 0);
+            let bread = &node.children[1];
+            assert_eq!(bread.children[0].text, "Wholegrain");
+        }
+    }
+
+    #[test]
+    fn test_numeric_ids_become_uuids() {
+        // Saved before ids were UUIDs, with Milk and Wholegrain both id 1
+        let node = NodeData::from_document(fixture(0)).unwrap();
+        let bread = &node.children[1];
+        let mut ids = vec![node.id, node.children[0].id, bread.id, bread.children[0].id];
+        ids.sort();
+        ids.dedup();
+        assert_eq!(ids.len(), 4);
+
+        // Nodes that have a UUID already keep it
+        let mut document = fixture(0);
+        document["id"] = json!("8722655e-f231-11ef-8932-1f1e2ee24d96");
+        let node = NodeData::from_document(document).unwrap();
+        assert_eq!(node.id.to_string(), "8722655e-f231-11ef-8932-1f1e2ee24d96");
+    }
+
+    #[test]
+    fn test_upgrade() {
+        let upgraded = upgrade(fixture(0)).unwrap();
+        assert_eq!(upgraded["version"], SCHEMA_VERSION);
+        assert_eq!(
+            upgraded["tree"]["children"][1]["children"][0]["done"],
+            false
+        );
+
+        // Every version ends up the same as what is written now
+        let current = NodeData::from_document(fixture(1)).unwrap().to_document();
+        assert_eq!(upgrade(fixture(1)).unwrap(), current);
+        assert_eq!(upgrade(current.clone()).unwrap(), current);
+    }
+
+    #[test]
+    fn test_rejects_unknown_versions() {
+        let mut document = fixture(2);
+        document["version"] = json!(SCHEMA_VERSION + 1);
+        assert_eq!(
+            NodeData::from_document(document).err(),
+            Some(NodeError::UnsupportedVersion(SCHEMA_VERSION + 1))
+        );
+
+        let mut document = fixture(2);
+        document["tree"]["children"][0]["is_open"] = json!("no");
+        assert_eq!(
+            NodeData::from_document(document).err(),
+            Some(NodeError::schema(
+                "$.tree.children[0].is_open",
+                "expected a boolean"
+            ))
+        );
+    }
+}
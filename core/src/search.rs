@@ -0,0 +1,102 @@
+use std::collections::HashSet;
+use std::ops::Range;
+
+use crate::{NodeData, NodeId};
+
+/// The byte ranges of `text` that match `query`, ignoring case, without
+/// overlaps. An empty query matches nothing.
+pub fn find_matches(text: &str, query: &str) -> Vec<Range<usize>> {
+    let query: Vec<char> = query.chars().flat_map(char::to_lowercase).collect();
+    if query.is_empty() {
+        return Vec::new();
+    }
+
+    let mut matches = Vec::new();
+    let mut next = 0;
+    for (start, _) in text.char_indices() {
+        if start < next {
+            continue;
+        }
+        // Matches `query` against the lowercased text from `start`, keeping
+        // track of the end of the last original character that was needed.
+        let mut remaining = query.iter();
+        let mut end = None;
+        for (offset, c) in text[start..].char_indices() {
+            let lower: Vec<char> = c.to_lowercase().collect();
+            let rest = remaining.as_slice();
+            if rest.len() < lower.len() || rest[..lower.len()] != lower[..] {
+                break;
+            }
+            remaining.nth(lower.len() - 1);
+            if remaining.as_slice().is_empty() {
+                end = Some(start + offset + c.len_utf8());
+                break;
+            }
+        }
+        if let Some(end) = end {
+            matches.push(start..end);
+            next = end;
+        }
+    }
+    matches
+}
+
+impl NodeData {
+    /// The ids of the nodes in the subtree rooted at `self` whose text
+    /// matches `query`, together with all of their ancestors, i.e. the nodes
+    /// to show when filtering the tree by `query`.
+    pub fn search(&self, query: &str) -> HashSet<NodeId> {
+        let mut visible = HashSet::new();
+        self.collect_search(query, &mut visible);
+        visible
+    }
+
+    fn collect_search(&self, query: &str, visible: &mut HashSet<NodeId>) -> bool {
+        let mut found = !find_matches(&self.text, query).is_empty();
+        for child in &self.children {
+            found |= child.collect_search(query, visible);
+        }
+        if found {
+            visible.insert(self.id);
+        }
+        found
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_matches() {
+        assert_eq!(find_matches("Buy milk", "milk"), vec![(4..8)]);
+        assert_eq!(find_matches("Buy MILK", "Milk"), vec![(4..8)]);
+        assert_eq!(find_matches("banana", "ana"), vec![(1..4)]);
+        assert_eq!(find_matches("aaaa", "aa"), [0..2, 2..4]);
+        assert!(find_matches("Buy milk", "").is_empty());
+        assert!(find_matches("Buy milk", "bread").is_empty());
+        // Offsets are in bytes of the original text
+        assert_eq!(find_matches("Größe GRÖSSE", "grö"), [0..4, 8..12]);
+    }
+
+    #[test]
+    fn test_search() {
+        let milk = NodeData::new(false, "Milk", vec![]);
+        let bread = NodeData::new(false, "Bread", vec![]);
+        let (milk_id, shopping) = (milk.id, NodeData::new(false, "Shopping", vec![milk, bread]));
+        let work = NodeData::new(false, "Work", vec![]);
+        let shopping_id = shopping.id;
+        let root = NodeData::new(true, "Root", vec![shopping, work]);
+
+        let visible = root.search("milk");
+        let expected: HashSet<_> = [root.id, shopping_id, milk_id].into();
+        assert_eq!(visible, expected);
+
+        // A matching parent does not bring in its children
+        let visible = root.search("shop");
+        let expected: HashSet<_> = [root.id, shopping_id].into();
+        assert_eq!(visible, expected);
+
+        assert!(root.search("nothing").is_empty());
+    }
+}
@@ -3,6 +3,8 @@ DROP TABLE IF EXISTS events CASCADE;
 CREATE TABLE events (
     id BIGSERIAL PRIMARY KEY,
     user_id BIGINT NOT NULL,
+    -- Chosen by the client, so that an event sent twice is stored once
+    event_id UUID,
     type TEXT NOT NULL,
     timestamp TIMESTAMPTZ NOT NULL DEFAULT NOW(),
     data JSONB
@@ -13,6 +15,9 @@ CREATE INDEX idx_events_data_jsonb ON events USING GIN (data);
 CREATE INDEX idx_events_user_id_id
   ON events (user_id, id);
 
+CREATE UNIQUE INDEX idx_events_user_id_event_id
+  ON events (user_id, event_id);
+
 DROP TABLE IF EXISTS states CASCADE;
 CREATE TABLE IF NOT EXISTS states (
     user_id BIGINT NOT NULL,
@@ -28,3 +33,18 @@ CREATE INDEX IF NOT EXISTS idx_state_jsonb
     ON states
     USING GIN (state);
 
+
+DROP TABLE IF EXISTS users CASCADE;
+CREATE TABLE users (
+    id BIGSERIAL PRIMARY KEY,
+    username TEXT NOT NULL UNIQUE,
+    password_hash TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+DROP TABLE IF EXISTS sessions CASCADE;
+CREATE TABLE sessions (
+    token_hash TEXT PRIMARY KEY,
+    user_id BIGINT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    expires_at TIMESTAMPTZ NOT NULL
+);
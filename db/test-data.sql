@@ -1,3 +1,8 @@
+-- A user "demo" with the password "password", who owns the events below.
+INSERT INTO users (id, username, password_hash)
+VALUES
+  (0, 'demo', 'pbkdf2-sha256$600000$HzCOpubaG6BXp38uIjtvbA$b/ZrKj0AGPva43nFmoz66kTDS/xUZ87HcWCMBtrIuXg');
+
 INSERT INTO events (id, user_id, type, data)
 VALUES 
   (1, 0, 'Added', '{"parent": null, "text": "make lunch", "id": "8722655e-f231-11ef-8932-1f1e2ee24d96" }'),
@@ -6,8 +11,11 @@ VALUES
   (4, 0, 'Added', '{"text": "add pesto", "parent": "8722655e-f231-11ef-8932-1f1e2ee24d96", "id": "3ae41864-f23a-11ef-885e-5763caa334a4" }'),
   (5, 0, 'MarkedAsDone', '{"id": "8722655e-f231-11ef-8932-1f1e2ee24d96" }');
 
+-- The rows above have explicit ids, so move the sequence past them.
+SELECT setval('events_id_seq', (SELECT MAX(id) FROM events));
+
 INSERT INTO states (user_id, after_event_id, state)
 VALUES
-    (0, 1, '{ "children": [{ "id": "8722655e-f231-11ef-8932-1f1e2ee24d96", "text": "make lunch" }] }'),
-    (0, 2, '{ "children": [{ "id": "8722655e-f231-11ef-8932-1f1e2ee24d96", "text": "make pasta for lunch" }] }'),
-    (0, 5, '{ "children": [] }');
\ No newline at end of file
+    (0, 1, '{ "children": [{ "id": "8722655e-f231-11ef-8932-1f1e2ee24d96", "is_open": false, "text": "make lunch", "done": false, "done_at": null, "children": [] }] }'),
+    (0, 2, '{ "children": [{ "id": "8722655e-f231-11ef-8932-1f1e2ee24d96", "is_open": false, "text": "make pasta for lunch", "done": false, "done_at": null, "children": [] }] }'),
+    (0, 5, '{ "children": [{ "id": "8722655e-f231-11ef-8932-1f1e2ee24d96", "is_open": false, "text": "make pasta for lunch", "done": true, "done_at": null, "children": [{ "id": "3ae41864-f23a-11ef-885e-5763caa334a4", "is_open": false, "text": "add pesto", "done": false, "done_at": null, "children": [] }, { "id": "1302f702-f23a-11ef-a65c-63c495723c09", "is_open": false, "text": "cook pasta", "done": false, "done_at": null, "children": [] }] }] }');
\ No newline at end of file
@@ -0,0 +1,288 @@
+use std::convert::Infallible;
+
+use axum::extract::{Path, Query, Request, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::middleware;
+use axum::response::sse::{self, KeepAlive, Sse};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use futures_util::stream::{Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tower_http::cors::CorsLayer;
+
+use crate::auth::{self, require_user, start_session, PasswordHasher, MIN_PASSWORD_LENGTH};
+use crate::live::Notifier;
+use crate::snapshot::Snapshotter;
+use crate::store::{EventId, NewEvent, Snapshot, Store, StoreError, StoredEvent, UserId};
+
+pub enum ApiError {
+    BadRequest(String),
+    /// No valid session, or wrong credentials.
+    Unauthorized(String),
+    /// A session of another user.
+    Forbidden,
+    NotFound,
+    Conflict(String),
+    Store(StoreError),
+}
+
+impl From<StoreError> for ApiError {
+    fn from(err: StoreError) -> Self {
+        ApiError::Store(err)
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let (status, message) = match self {
+            ApiError::BadRequest(message) => (StatusCode::BAD_REQUEST, message),
+            ApiError::Unauthorized(message) => (StatusCode::UNAUTHORIZED, message),
+            ApiError::Forbidden => (StatusCode::FORBIDDEN, "forbidden".to_string()),
+            ApiError::NotFound => (StatusCode::NOT_FOUND, "not found".to_string()),
+            ApiError::Conflict(message) => (StatusCode::CONFLICT, message),
+            ApiError::Store(err) => {
+                eprintln!("{}", err);
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "internal error".to_string(),
+                )
+            }
+        };
+        (status, Json(json!({ "error": message }))).into_response()
+    }
+}
+
+#[derive(Clone)]
+pub struct AppState<S> {
+    pub store: S,
+    pub snapshotter: Snapshotter,
+    pub hasher: PasswordHasher,
+    pub notifier: Notifier,
+}
+
+#[derive(Deserialize)]
+pub struct EventsQuery {
+    #[serde(default)]
+    after: EventId,
+}
+
+#[derive(Deserialize)]
+pub struct Credentials {
+    username: String,
+    password: String,
+}
+
+/// The answer to signing up or logging in. `token` goes into the
+/// `Authorization: Bearer` header of later requests.
+#[derive(Serialize)]
+pub struct Session {
+    user_id: UserId,
+    token: String,
+}
+
+/// Builds the HTTP API:
+///
+/// - `POST /signup` creates a user from `{username, password}` and returns a
+///   `Session`.
+/// - `POST /login` returns a new `Session` for `{username, password}`.
+/// - `POST /logout` ends the session of the request.
+///
+/// All routes under `/users/{user_id}` need a session of that user:
+///
+/// - `POST /users/{user_id}/events` appends a JSON array of
+///   `{event_id, type, data}` events and returns the stored rows. Events
+///   whose `event_id` was appended before are skipped.
+/// - `GET /users/{user_id}/events?after={id}` returns the events after `id`.
+/// - `GET /users/{user_id}/events/stream?after={id}` streams the events after
+///   `id` as server-sent events, each a JSON array of rows, as they are
+///   appended. A reconnecting `EventSource` resumes after its `Last-Event-ID`.
+/// - `GET /users/{user_id}/state` returns the latest snapshot.
+/// - `GET /users/{user_id}/document` returns the current document, built from
+///   the latest snapshot and the events after it.
+pub fn router<S: Store>(store: S, snapshotter: Snapshotter, hasher: PasswordHasher) -> Router {
+    let state = AppState {
+        store,
+        snapshotter,
+        hasher,
+        notifier: Notifier::new(),
+    };
+    let user_routes = Router::new()
+        .route("/events", get(events_after::<S>).post(append_events::<S>))
+        .route("/events/stream", get(stream_events::<S>))
+        .route("/state", get(latest_snapshot::<S>))
+        .route("/document", get(document::<S>))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            require_user::<S>,
+        ));
+    Router::new()
+        .route("/signup", post(signup::<S>))
+        .route("/login", post(login::<S>))
+        .route("/logout", post(logout::<S>))
+        .nest("/users/{user_id}", user_routes)
+        .layer(CorsLayer::permissive())
+        .with_state(state)
+}
+
+async fn signup<S: Store>(
+    State(AppState { store, hasher, .. }): State<AppState<S>>,
+    Json(credentials): Json<Credentials>,
+) -> Result<(StatusCode, Json<Session>), ApiError> {
+    let username = credentials.username.trim().to_string();
+    if username.is_empty() {
+        return Err(ApiError::BadRequest(
+            "username must not be empty".to_string(),
+        ));
+    }
+    if credentials.password.chars().count() < MIN_PASSWORD_LENGTH {
+        return Err(ApiError::BadRequest(format!(
+            "password must have at least {} characters",
+            MIN_PASSWORD_LENGTH
+        )));
+    }
+
+    let password_hash = hash_blocking(hasher, credentials.password).await;
+    let Some(user) = store.create_user(username, password_hash).await? else {
+        return Err(ApiError::Conflict("username is taken".to_string()));
+    };
+    let token = start_session(&store, user.id).await?;
+    Ok((
+        StatusCode::CREATED,
+        Json(Session {
+            user_id: user.id,
+            token,
+        }),
+    ))
+}
+
+async fn login<S: Store>(
+    State(AppState { store, hasher, .. }): State<AppState<S>>,
+    Json(credentials): Json<Credentials>,
+) -> Result<Json<Session>, ApiError> {
+    let user = store
+        .user_by_name(credentials.username.trim().to_string())
+        .await?;
+    let hash = user.as_ref().map(|user| user.password_hash.clone());
+    let verified = verify_blocking(hasher, credentials.password, hash).await;
+    match user {
+        Some(user) if verified => Ok(Json(Session {
+            user_id: user.id,
+            token: start_session(&store, user.id).await?,
+        })),
+        _ => Err(ApiError::Unauthorized(
+            "invalid username or password".to_string(),
+        )),
+    }
+}
+
+async fn logout<S: Store>(
+    State(AppState { store, .. }): State<AppState<S>>,
+    request: Request,
+) -> Result<StatusCode, ApiError> {
+    if let Some(token) = auth::request_token(request.headers(), request.uri().query()) {
+        store.delete_session(auth::token_hash(&token)).await?;
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Hashing takes a good fraction of a second on purpose, so it runs off the
+/// threads serving requests.
+async fn hash_blocking(hasher: PasswordHasher, password: String) -> String {
+    tokio::task::spawn_blocking(move || hasher.hash(&password))
+        .await
+        .expect("hashing panicked")
+}
+
+/// Checks `password` against `hash`, or for an unknown user with no hash
+/// takes the same time to fail.
+async fn verify_blocking(hasher: PasswordHasher, password: String, hash: Option<String>) -> bool {
+    tokio::task::spawn_blocking(move || match hash {
+        Some(hash) => auth::verify_password(&password, &hash),
+        None => hasher.verify_nothing(&password),
+    })
+    .await
+    .expect("hashing panicked")
+}
+
+async fn append_events<S: Store>(
+    State(AppState {
+        store,
+        snapshotter,
+        notifier,
+        ..
+    }): State<AppState<S>>,
+    Path(user_id): Path<UserId>,
+    Json(events): Json<Vec<NewEvent>>,
+) -> Result<(StatusCode, Json<Vec<StoredEvent>>), ApiError> {
+    if let Some(event) = events.iter().find(|event| event.event_type.is_empty()) {
+        return Err(ApiError::BadRequest(format!(
+            "event type must not be empty: {}",
+            event.data
+        )));
+    }
+    let stored = store.append_events(user_id, events).await?;
+    notifier.notify(user_id);
+    // The events are stored at this point, so a failed snapshot only means
+    // the next load replays a few more of them.
+    if let Err(err) = snapshotter.maybe_snapshot(&store, user_id).await {
+        eprintln!("Failed to write snapshot for user {}: {}", user_id, err);
+    }
+    Ok((StatusCode::CREATED, Json(stored)))
+}
+
+async fn events_after<S: Store>(
+    State(AppState { store, .. }): State<AppState<S>>,
+    Path(user_id): Path<UserId>,
+    Query(query): Query<EventsQuery>,
+) -> Result<Json<Vec<StoredEvent>>, ApiError> {
+    Ok(Json(store.events_after(user_id, query.after).await?))
+}
+
+async fn stream_events<S: Store>(
+    State(AppState {
+        store, notifier, ..
+    }): State<AppState<S>>,
+    Path(user_id): Path<UserId>,
+    Query(query): Query<EventsQuery>,
+    headers: HeaderMap,
+) -> Sse<impl Stream<Item = Result<sse::Event, Infallible>>> {
+    let after = headers
+        .get("last-event-id")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(query.after);
+    let events = notifier.subscribe(store, user_id, after).map(|rows| {
+        let last = rows.last().map_or(0, |row| row.id);
+        Ok(sse::Event::default()
+            .id(last.to_string())
+            .json_data(rows)
+            .expect("rows serialize to JSON"))
+    });
+    Sse::new(events).keep_alive(KeepAlive::default())
+}
+
+async fn latest_snapshot<S: Store>(
+    State(AppState { store, .. }): State<AppState<S>>,
+    Path(user_id): Path<UserId>,
+) -> Result<Json<Snapshot>, ApiError> {
+    match store.latest_snapshot(user_id).await? {
+        Some(snapshot) => Ok(Json(snapshot)),
+        None => Err(ApiError::NotFound),
+    }
+}
+
+async fn document<S: Store>(
+    State(AppState {
+        store, snapshotter, ..
+    }): State<AppState<S>>,
+    Path(user_id): Path<UserId>,
+) -> Result<Json<Snapshot>, ApiError> {
+    let loaded = snapshotter.load(&store, user_id).await?;
+    Ok(Json(Snapshot {
+        user_id,
+        after_event_id: loaded.after_event_id,
+        state: loaded.state.to_json(),
+    }))
+}
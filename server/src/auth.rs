@@ -0,0 +1,233 @@
+use axum::extract::{Path, Request, State};
+use axum::http::header::AUTHORIZATION;
+use axum::http::HeaderMap;
+use axum::middleware::Next;
+use axum::response::Response;
+use base64::engine::general_purpose::{STANDARD_NO_PAD, URL_SAFE_NO_PAD};
+use base64::Engine;
+use chrono::{TimeDelta, Utc};
+use pbkdf2::pbkdf2_hmac_array;
+use sha2::{Digest, Sha256};
+
+use crate::api::{ApiError, AppState};
+use crate::store::{Store, UserId};
+
+/// PBKDF2-HMAC-SHA256 rounds for new password hashes used when
+/// `PASSWORD_ITERATIONS` is not set, as recommended by OWASP.
+pub const DEFAULT_ITERATIONS: u32 = 600_000;
+/// How long a session lasts after signing up or logging in.
+pub const SESSION_TTL: TimeDelta = TimeDelta::days(30);
+/// Passwords shorter than this are rejected at signup.
+pub const MIN_PASSWORD_LENGTH: usize = 8;
+
+const SCHEME: &str = "pbkdf2-sha256";
+
+fn random_bytes<const N: usize>() -> [u8; N] {
+    let mut bytes = [0; N];
+    getrandom::fill(&mut bytes).expect("the system random number generator failed");
+    bytes
+}
+
+/// Hashes new passwords with PBKDF2-HMAC-SHA256. Hashes record their own
+/// iteration count, so it can be changed without invalidating them.
+#[derive(Clone, Copy, Debug)]
+pub struct PasswordHasher {
+    pub iterations: u32,
+}
+
+impl Default for PasswordHasher {
+    fn default() -> Self {
+        Self {
+            iterations: DEFAULT_ITERATIONS,
+        }
+    }
+}
+
+impl PasswordHasher {
+    pub fn new(iterations: u32) -> Self {
+        Self {
+            iterations: iterations.max(1),
+        }
+    }
+
+    /// Reads the iteration count from `PASSWORD_ITERATIONS`, falling back to
+    /// the default if it is unset or not a positive number.
+    pub fn from_env() -> Self {
+        std::env::var("PASSWORD_ITERATIONS")
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .filter(|&iterations| iterations > 0)
+            .map(Self::new)
+            .unwrap_or_default()
+    }
+
+    /// Hashes `password` with a random salt, as
+    /// `pbkdf2-sha256$<iterations>$<salt>$<hash>` in base64.
+    pub fn hash(&self, password: &str) -> String {
+        hash_with(password, &random_bytes::<16>(), self.iterations)
+    }
+
+    /// Checks `password` against a hash that matches no password, taking as
+    /// long as checking it against a real one. Used for unknown usernames,
+    /// so that the time a failed login takes does not tell which exist.
+    pub fn verify_nothing(&self, password: &str) -> bool {
+        let dummy = format!(
+            "{}${}${}${}",
+            SCHEME,
+            self.iterations,
+            STANDARD_NO_PAD.encode([0; 16]),
+            STANDARD_NO_PAD.encode([0; 32])
+        );
+        verify_password(password, &dummy)
+    }
+}
+
+fn hash_with(password: &str, salt: &[u8], iterations: u32) -> String {
+    let hash = pbkdf2_hmac_array::<Sha256, 32>(password.as_bytes(), salt, iterations);
+    format!(
+        "{}${}${}${}",
+        SCHEME,
+        iterations,
+        STANDARD_NO_PAD.encode(salt),
+        STANDARD_NO_PAD.encode(hash)
+    )
+}
+
+/// Whether `password` matches a hash from `PasswordHasher::hash`.
+pub fn verify_password(password: &str, hash: &str) -> bool {
+    let mut parts = hash.split('$');
+    let (Some(SCHEME), Some(iterations), Some(salt), Some(expected), None) = (
+        parts.next(),
+        parts.next(),
+        parts.next(),
+        parts.next(),
+        parts.next(),
+    ) else {
+        return false;
+    };
+    let (Ok(iterations), Ok(salt), Ok(expected)) = (
+        iterations.parse(),
+        STANDARD_NO_PAD.decode(salt),
+        STANDARD_NO_PAD.decode(expected),
+    ) else {
+        return false;
+    };
+    let actual = pbkdf2_hmac_array::<Sha256, 32>(password.as_bytes(), &salt, iterations);
+    // Compares every byte, so the time taken does not tell how many matched
+    actual.len() == expected.len()
+        && actual
+            .iter()
+            .zip(&expected)
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+/// A new random session token.
+pub fn new_token() -> String {
+    URL_SAFE_NO_PAD.encode(random_bytes::<32>())
+}
+
+/// What the store keeps of a token, so that reading the `sessions` table
+/// does not give access to any account.
+pub fn token_hash(token: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(token.as_bytes()))
+}
+
+/// The token of a request, from an `Authorization: Bearer` header or else an
+/// `access_token` query parameter, which is all an `EventSource` can send.
+pub fn request_token(headers: &HeaderMap, query: Option<&str>) -> Option<String> {
+    let from_header = headers
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(|token| token.trim().to_string());
+    from_header.or_else(|| {
+        query?.split('&').find_map(|pair| {
+            pair.strip_prefix("access_token=")
+                .map(|token| token.to_string())
+        })
+    })
+}
+
+/// Lets requests to `/users/{user_id}/...` through only with a session of
+/// that user.
+pub async fn require_user<S: Store>(
+    State(AppState { store, .. }): State<AppState<S>>,
+    Path(user_id): Path<UserId>,
+    request: Request,
+    next: Next,
+) -> Result<Response, ApiError> {
+    let Some(token) = request_token(request.headers(), request.uri().query()) else {
+        return Err(ApiError::Unauthorized("not logged in".to_string()));
+    };
+    match store.session_user(token_hash(&token)).await? {
+        None => Err(ApiError::Unauthorized("session expired".to_string())),
+        Some(session_user) if session_user != user_id => Err(ApiError::Forbidden),
+        Some(_) => Ok(next.run(request).await),
+    }
+}
+
+/// Starts a session of `user_id` and returns its token.
+pub async fn start_session<S: Store>(store: &S, user_id: UserId) -> Result<String, ApiError> {
+    let token = new_token();
+    store
+        .create_session(token_hash(&token), user_id, Utc::now() + SESSION_TTL)
+        .await?;
+    Ok(token)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pbkdf2_matches_rfc_7914() {
+        // The PBKDF2-HMAC-SHA256 test vector from RFC 7914, section 11, which
+        // the hashes already stored were made with
+        let hash = pbkdf2_hmac_array::<Sha256, 32>(b"passwd", b"salt", 1);
+        assert_eq!(
+            hash[..16],
+            [
+                0x55, 0xac, 0x04, 0x6e, 0x56, 0xe3, 0x08, 0x9f, 0xec, 0x16, 0x91, 0xc2, 0x25, 0x44,
+                0xb6, 0x05
+            ]
+        );
+    }
+
+    #[test]
+    fn test_verify_password() {
+        let hash = hash_with("correct horse", b"some salt", 10);
+        assert!(hash.starts_with("pbkdf2-sha256$10$"));
+        assert!(verify_password("correct horse", &hash));
+        assert!(!verify_password("correct horse ", &hash));
+        assert!(!verify_password("correct horse", "plain text"));
+        assert!(!verify_password(
+            "correct horse",
+            &format!("{}$extra", hash)
+        ));
+        assert_ne!(hash_with("correct horse", b"other salt", 10), hash);
+
+        let hasher = PasswordHasher::new(10);
+        let hash = hasher.hash("correct horse");
+        assert!(hash.starts_with("pbkdf2-sha256$10$"));
+        assert!(verify_password("correct horse", &hash));
+        assert_ne!(hasher.hash("correct horse"), hash);
+        assert!(!hasher.verify_nothing("correct horse"));
+        assert!(!hasher.verify_nothing(""));
+    }
+
+    #[test]
+    fn test_request_token() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(request_token(&headers, None), None);
+        assert_eq!(
+            request_token(&headers, Some("after=3&access_token=abc")),
+            Some("abc".to_string())
+        );
+        headers.insert(AUTHORIZATION, "Bearer xyz".parse().unwrap());
+        assert_eq!(
+            request_token(&headers, Some("access_token=abc")),
+            Some("xyz".to_string())
+        );
+    }
+}
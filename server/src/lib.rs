@@ -0,0 +1,7 @@
+pub mod api;
+pub mod auth;
+pub mod live;
+pub mod postgres;
+pub mod snapshot;
+pub mod state;
+pub mod store;
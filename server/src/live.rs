@@ -0,0 +1,87 @@
+use std::time::Duration;
+
+use futures_util::stream::{self, Stream};
+use tokio::sync::broadcast::{self, error::RecvError};
+
+use crate::store::{EventId, Store, StoredEvent, UserId};
+
+/// How often a subscription looks for new rows without being notified, which
+/// picks up events appended through another server process.
+pub const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Tells subscriptions in this process that a user has new events.
+#[derive(Clone)]
+pub struct Notifier {
+    sender: broadcast::Sender<UserId>,
+}
+
+impl Default for Notifier {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Notifier {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(256);
+        Self { sender }
+    }
+
+    /// Wakes up the subscriptions of `user_id`.
+    pub fn notify(&self, user_id: UserId) {
+        // Fails only when nobody is subscribed
+        let _ = self.sender.send(user_id);
+    }
+
+    /// The rows of `user_id` with an id greater than `after`, in batches as
+    /// they are appended. The first batch holds the rows already stored, if
+    /// there are any. The stream ends when the store fails, so that the
+    /// client reconnects.
+    pub fn subscribe<S: Store>(
+        &self,
+        store: S,
+        user_id: UserId,
+        after: EventId,
+    ) -> impl Stream<Item = Vec<StoredEvent>> + Send + 'static {
+        let receiver = self.sender.subscribe();
+        stream::unfold(
+            (store, receiver, after),
+            move |(store, mut receiver, after)| async move {
+                loop {
+                    match store.events_after(user_id, after).await {
+                        Ok(rows) if !rows.is_empty() => {
+                            let after = rows.last().map_or(after, |row| row.id);
+                            return Some((rows, (store, receiver, after)));
+                        }
+                        Ok(_) => {}
+                        Err(err) => {
+                            eprintln!("Failed to read events of user {}: {}", user_id, err);
+                            return None;
+                        }
+                    }
+                    if !wait(&mut receiver, user_id).await {
+                        return None;
+                    }
+                }
+            },
+        )
+    }
+}
+
+/// Waits until `user_id` is notified or `POLL_INTERVAL` has passed. `false`
+/// if the notifier is gone.
+async fn wait(receiver: &mut broadcast::Receiver<UserId>, user_id: UserId) -> bool {
+    let notified = async {
+        loop {
+            match receiver.recv().await {
+                Ok(notified) if notified != user_id => continue,
+                // Missed notifications are caught up on by the next query
+                Ok(_) | Err(RecvError::Lagged(_)) => return true,
+                Err(RecvError::Closed) => return false,
+            }
+        }
+    };
+    tokio::time::timeout(POLL_INTERVAL, notified)
+        .await
+        .unwrap_or(true)
+}
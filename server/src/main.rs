@@ -0,0 +1,33 @@
+use notes_server::api;
+use notes_server::auth::PasswordHasher;
+use notes_server::postgres::PostgresStore;
+use notes_server::snapshot::Snapshotter;
+use notes_server::store::MemoryStore;
+
+#[tokio::main]
+async fn main() {
+    let addr = std::env::var("BIND_ADDR").unwrap_or_else(|_| "0.0.0.0:8000".to_string());
+    let listener = tokio::net::TcpListener::bind(&addr)
+        .await
+        .unwrap_or_else(|err| panic!("Failed to bind {}: {}", addr, err));
+
+    let snapshotter = Snapshotter::from_env();
+    let hasher = PasswordHasher::from_env();
+
+    // Without a database the server keeps everything in memory, which is
+    // handy for trying out the client.
+    let app = match std::env::var("DATABASE_URL") {
+        Ok(url) => {
+            let store = PostgresStore::connect(&url).expect("Failed to configure database");
+            store.migrate().await.expect("Failed to create tables");
+            api::router(store, snapshotter, hasher)
+        }
+        Err(_) => {
+            eprintln!("DATABASE_URL not set, using in-memory storage");
+            api::router(MemoryStore::new(), snapshotter, hasher)
+        }
+    };
+
+    println!("Listening on {}", addr);
+    axum::serve(listener, app).await.expect("Server error");
+}
@@ -0,0 +1,101 @@
+use crate::state::DocumentState;
+use crate::store::{EventId, Snapshot, Store, StoreError, UserId};
+
+/// Snapshot interval used when `SNAPSHOT_INTERVAL` is not set.
+pub const DEFAULT_INTERVAL: usize = 100;
+
+/// Materializes documents from the event log and writes them to the
+/// `states` table every `interval` events.
+#[derive(Clone, Copy, Debug)]
+pub struct Snapshotter {
+    pub interval: usize,
+}
+
+impl Default for Snapshotter {
+    fn default() -> Self {
+        Self {
+            interval: DEFAULT_INTERVAL,
+        }
+    }
+}
+
+/// A document together with the id of the last event folded into it.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Loaded {
+    pub state: DocumentState,
+    pub after_event_id: EventId,
+    /// How many events were replayed on top of the newest snapshot.
+    pub replayed: usize,
+}
+
+impl Snapshotter {
+    pub fn new(interval: usize) -> Self {
+        Self {
+            interval: interval.max(1),
+        }
+    }
+
+    /// Reads the interval from `SNAPSHOT_INTERVAL`, falling back to the
+    /// default if it is unset or not a positive number.
+    pub fn from_env() -> Self {
+        std::env::var("SNAPSHOT_INTERVAL")
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .filter(|&interval| interval > 0)
+            .map(Self::new)
+            .unwrap_or_default()
+    }
+
+    /// Loads the current document of `user_id`, starting from the newest
+    /// snapshot and replaying only the events after it. A snapshot that
+    /// cannot be read, e.g. one written by an older version, is ignored and
+    /// the document replayed from the first event instead.
+    pub async fn load<S: Store>(&self, store: &S, user_id: UserId) -> Result<Loaded, StoreError> {
+        let (mut state, after) = match store.latest_snapshot(user_id).await? {
+            Some(snapshot) => match DocumentState::from_json(&snapshot.state) {
+                Ok(state) => (state, snapshot.after_event_id),
+                Err(err) => {
+                    eprintln!(
+                        "Ignoring snapshot of user {} after event {}: {}",
+                        user_id, snapshot.after_event_id, err
+                    );
+                    (DocumentState::default(), 0)
+                }
+            },
+            None => (DocumentState::default(), 0),
+        };
+
+        let events = store.events_after(user_id, after).await?;
+        for event in &events {
+            state.apply(&event.event_type, &event.data);
+        }
+
+        Ok(Loaded {
+            state,
+            after_event_id: events.last().map_or(after, |event| event.id),
+            replayed: events.len(),
+        })
+    }
+
+    /// Writes a new snapshot of `user_id` if at least `interval` events have
+    /// been appended since the newest one. Returns the snapshot if one was
+    /// written.
+    pub async fn maybe_snapshot<S: Store>(
+        &self,
+        store: &S,
+        user_id: UserId,
+    ) -> Result<Option<Snapshot>, StoreError> {
+        let loaded = self.load(store, user_id).await?;
+        if loaded.replayed < self.interval {
+            return Ok(None);
+        }
+
+        let snapshot = Snapshot {
+            user_id,
+            after_event_id: loaded.after_event_id,
+            state: loaded.state.to_json(),
+        };
+        store.save_snapshot(snapshot.clone()).await?;
+        Ok(Some(snapshot))
+    }
+}
@@ -0,0 +1,179 @@
+use notes_core::{Event, NodeData, NodeError, NodeId};
+use serde_json::{json, Value};
+
+/// A user's whole document, materialized from their events with the same
+/// reducer as the client.
+///
+/// The top-level nodes are kept as the children of a root standing in for
+/// the client's, which events refer to as a `null` parent. The `state` column
+/// holds only those: `{"children": [...]}`, each as `NodeData::to_json`
+/// writes it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DocumentState {
+    root: NodeData,
+}
+
+impl Default for DocumentState {
+    fn default() -> Self {
+        Self {
+            root: NodeData::with_id(NodeId::nil(), true, "", vec![]),
+        }
+    }
+}
+
+impl DocumentState {
+    /// The top-level nodes.
+    pub fn children(&self) -> &[NodeData] {
+        &self.root.children
+    }
+
+    /// Reads a `state` column. Errors point at the offending value, e.g.
+    /// `$.children[0].is_open`.
+    pub fn from_json(value: &Value) -> Result<Self, NodeError> {
+        let mut root = Self::default().root.to_json();
+        root["children"] = value["children"].clone();
+        Ok(Self {
+            root: NodeData::from_json(&root)?,
+        })
+    }
+
+    pub fn to_json(&self) -> Value {
+        let children: Vec<Value> = self.children().iter().map(NodeData::to_json).collect();
+        json!({ "children": children })
+    }
+
+    /// Applies an event given as the `type` and `data` columns of an `events`
+    /// row. Returns `false` and leaves the state unchanged for unknown or
+    /// inapplicable events.
+    pub fn apply(&mut self, event_type: &str, data: &Value) -> bool {
+        Event::from_parts(event_type, data).is_some_and(|event| self.root.apply(&event))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LUNCH: &str = "8722655e-f231-11ef-8932-1f1e2ee24d96";
+    const PASTA: &str = "1302f702-f23a-11ef-a65c-63c495723c09";
+    const PESTO: &str = "3ae41864-f23a-11ef-885e-5763caa334a4";
+
+    fn texts(nodes: &[NodeData]) -> Vec<&str> {
+        nodes.iter().map(|node| node.text.as_str()).collect()
+    }
+
+    #[test]
+    fn test_apply_test_data() {
+        // The events from db/test-data.sql
+        let mut state = DocumentState::default();
+        assert!(state.apply(
+            "Added",
+            &json!({ "parent": null, "text": "make lunch", "id": LUNCH })
+        ));
+        assert!(state.apply(
+            "Edited",
+            &json!({ "text": "make pasta for lunch", "id": LUNCH })
+        ));
+        assert!(state.apply(
+            "Added",
+            &json!({ "text": "cook pasta", "parent": LUNCH, "id": PASTA })
+        ));
+        assert!(state.apply(
+            "Added",
+            &json!({ "text": "add pesto", "parent": LUNCH, "id": PESTO })
+        ));
+        assert!(state.apply("MarkedAsDone", &json!({ "id": LUNCH })));
+
+        assert_eq!(texts(state.children()), ["make pasta for lunch"]);
+        assert!(state.children()[0].done);
+        assert_eq!(
+            texts(&state.children()[0].children),
+            ["add pesto", "cook pasta"]
+        );
+    }
+
+    #[test]
+    fn test_apply_moves_and_removals() {
+        let mut state = DocumentState::default();
+        state.apply(
+            "Added",
+            &json!({ "parent": null, "index": 0, "text": "lunch", "id": LUNCH }),
+        );
+        state.apply(
+            "Added",
+            &json!({ "parent": null, "index": 1, "text": "pasta", "id": PASTA }),
+        );
+        state.apply(
+            "Added",
+            &json!({ "parent": null, "index": 2, "text": "pesto", "id": PESTO }),
+        );
+
+        assert!(state.apply(
+            "Moved",
+            &json!({ "id": PASTA, "parent": LUNCH, "index": 0 })
+        ));
+        assert!(state.apply("Moved", &json!({ "id": PESTO, "parent": null, "index": 0 })));
+        assert_eq!(texts(state.children()), ["pesto", "lunch"]);
+        assert_eq!(texts(&state.children()[1].children), ["pasta"]);
+
+        // No cycles, no unknown parents
+        let before = state.clone();
+        assert!(!state.apply(
+            "Moved",
+            &json!({ "id": LUNCH, "parent": PASTA, "index": 0 })
+        ));
+        assert!(!state.apply(
+            "Moved",
+            &json!({ "id": LUNCH, "parent": LUNCH, "index": 0 })
+        ));
+        assert!(!state.apply(
+            "Moved",
+            &json!({ "id": PESTO, "parent": "8c0b7f4e-f23a-11ef-9d1e-3f6b2a7c5d90", "index": 0 })
+        ));
+        assert_eq!(state, before);
+
+        assert!(state.apply("Removed", &json!({ "id": LUNCH })));
+        assert_eq!(texts(state.children()), ["pesto"]);
+        assert!(!state.apply("Edited", &json!({ "id": PASTA, "text": "gone" })));
+        assert!(!state.apply("Unknown", &json!({ "id": PESTO })));
+    }
+
+    #[test]
+    fn test_apply_keeps_fold_state() {
+        let mut state = DocumentState::default();
+        state.apply(
+            "Added",
+            &json!({ "parent": null, "index": 0, "text": "lunch", "id": LUNCH }),
+        );
+        assert!(!state.children()[0].is_open);
+        assert!(state.apply("Folded", &json!({ "id": LUNCH, "open": true })));
+        assert!(state.children()[0].is_open);
+        assert_eq!(DocumentState::from_json(&state.to_json()), Ok(state));
+    }
+
+    #[test]
+    fn test_json_roundtrip() {
+        // A snapshot from db/test-data.sql
+        let json = json!({ "children": [{
+            "id": LUNCH,
+            "is_open": false,
+            "text": "make lunch",
+            "done": false,
+            "done_at": null,
+            "children": []
+        }] });
+        let state = DocumentState::from_json(&json).unwrap();
+        assert_eq!(texts(state.children()), ["make lunch"]);
+        assert_eq!(state.to_json(), json);
+
+        // Snapshots written before the server kept fold state
+        let json = json!({ "children": [{ "id": LUNCH, "text": "make lunch" }] });
+        assert_eq!(
+            DocumentState::from_json(&json),
+            Err(NodeError::schema(
+                "$.children[0].is_open",
+                "expected a boolean"
+            ))
+        );
+    }
+}
@@ -0,0 +1,273 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::future::Future;
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+pub type UserId = i64;
+pub type EventId = i64;
+
+/// An event as sent by a client, before it has been assigned an id.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct NewEvent {
+    /// Chosen by the client so that sending the event again, e.g. after a
+    /// lost response, does not append it twice.
+    #[serde(default)]
+    pub event_id: Option<Uuid>,
+    #[serde(rename = "type")]
+    pub event_type: String,
+    #[serde(default)]
+    pub data: Value,
+}
+
+/// A row of the `events` table.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct StoredEvent {
+    pub id: EventId,
+    /// `None` for events appended before clients chose ids.
+    pub event_id: Option<Uuid>,
+    pub user_id: UserId,
+    #[serde(rename = "type")]
+    pub event_type: String,
+    pub timestamp: DateTime<Utc>,
+    pub data: Value,
+}
+
+/// A row of the `states` table: the document of `user_id` after applying all
+/// events up to and including `after_event_id`.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Snapshot {
+    pub user_id: UserId,
+    pub after_event_id: EventId,
+    pub state: Value,
+}
+
+/// A row of the `users` table.
+#[derive(Clone, Debug, PartialEq)]
+pub struct User {
+    pub id: UserId,
+    pub username: String,
+    /// See `auth::PasswordHasher::hash`.
+    pub password_hash: String,
+}
+
+#[derive(Debug)]
+pub enum StoreError {
+    Database(String),
+}
+
+impl fmt::Display for StoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StoreError::Database(message) => write!(f, "database error: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for StoreError {}
+
+/// Persistence for the events API, implemented over Postgres and in memory.
+pub trait Store: Clone + Send + Sync + 'static {
+    /// Appends `events` for `user_id` atomically, in order, and returns the
+    /// stored rows. Events with an `event_id` the user already has are
+    /// skipped.
+    fn append_events(
+        &self,
+        user_id: UserId,
+        events: Vec<NewEvent>,
+    ) -> impl Future<Output = Result<Vec<StoredEvent>, StoreError>> + Send;
+
+    /// All events of `user_id` with an id greater than `after`, oldest first.
+    fn events_after(
+        &self,
+        user_id: UserId,
+        after: EventId,
+    ) -> impl Future<Output = Result<Vec<StoredEvent>, StoreError>> + Send;
+
+    /// The snapshot of `user_id` with the highest `after_event_id`.
+    fn latest_snapshot(
+        &self,
+        user_id: UserId,
+    ) -> impl Future<Output = Result<Option<Snapshot>, StoreError>> + Send;
+
+    /// Stores `snapshot`, replacing any existing one for the same event.
+    fn save_snapshot(
+        &self,
+        snapshot: Snapshot,
+    ) -> impl Future<Output = Result<(), StoreError>> + Send;
+
+    /// Adds a user, or returns `None` if `username` is taken.
+    fn create_user(
+        &self,
+        username: String,
+        password_hash: String,
+    ) -> impl Future<Output = Result<Option<User>, StoreError>> + Send;
+
+    fn user_by_name(
+        &self,
+        username: String,
+    ) -> impl Future<Output = Result<Option<User>, StoreError>> + Send;
+
+    /// Stores a session of `user_id` under the hash of its token, valid
+    /// until `expires_at`.
+    fn create_session(
+        &self,
+        token_hash: String,
+        user_id: UserId,
+        expires_at: DateTime<Utc>,
+    ) -> impl Future<Output = Result<(), StoreError>> + Send;
+
+    /// The user of the session with `token_hash`, if it has not expired.
+    fn session_user(
+        &self,
+        token_hash: String,
+    ) -> impl Future<Output = Result<Option<UserId>, StoreError>> + Send;
+
+    fn delete_session(
+        &self,
+        token_hash: String,
+    ) -> impl Future<Output = Result<(), StoreError>> + Send;
+}
+
+#[derive(Default)]
+struct Memory {
+    events: Vec<StoredEvent>,
+    snapshots: HashMap<UserId, Vec<Snapshot>>,
+    users: Vec<User>,
+    sessions: HashMap<String, (UserId, DateTime<Utc>)>,
+}
+
+/// A `Store` kept in process memory, used in tests and for running the
+/// server without a database.
+#[derive(Clone, Default)]
+pub struct MemoryStore {
+    memory: Arc<Mutex<Memory>>,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Store for MemoryStore {
+    async fn append_events(
+        &self,
+        user_id: UserId,
+        events: Vec<NewEvent>,
+    ) -> Result<Vec<StoredEvent>, StoreError> {
+        let mut memory = self.memory.lock().await;
+        let timestamp = Utc::now();
+        let mut stored = Vec::with_capacity(events.len());
+        for event in events {
+            let duplicate = event.event_id.is_some()
+                && memory.events.iter().any(|existing| {
+                    existing.user_id == user_id && existing.event_id == event.event_id
+                });
+            if duplicate {
+                continue;
+            }
+            let event = StoredEvent {
+                id: memory.events.len() as EventId + 1,
+                event_id: event.event_id,
+                user_id,
+                event_type: event.event_type,
+                timestamp,
+                data: event.data,
+            };
+            memory.events.push(event.clone());
+            stored.push(event);
+        }
+        Ok(stored)
+    }
+
+    async fn events_after(
+        &self,
+        user_id: UserId,
+        after: EventId,
+    ) -> Result<Vec<StoredEvent>, StoreError> {
+        let memory = self.memory.lock().await;
+        Ok(memory
+            .events
+            .iter()
+            .filter(|event| event.user_id == user_id && event.id > after)
+            .cloned()
+            .collect())
+    }
+
+    async fn latest_snapshot(&self, user_id: UserId) -> Result<Option<Snapshot>, StoreError> {
+        let memory = self.memory.lock().await;
+        Ok(memory.snapshots.get(&user_id).and_then(|snapshots| {
+            snapshots
+                .iter()
+                .max_by_key(|snapshot| snapshot.after_event_id)
+                .cloned()
+        }))
+    }
+
+    async fn save_snapshot(&self, snapshot: Snapshot) -> Result<(), StoreError> {
+        let mut memory = self.memory.lock().await;
+        let snapshots = memory.snapshots.entry(snapshot.user_id).or_default();
+        snapshots.retain(|existing| existing.after_event_id != snapshot.after_event_id);
+        snapshots.push(snapshot);
+        Ok(())
+    }
+
+    async fn create_user(
+        &self,
+        username: String,
+        password_hash: String,
+    ) -> Result<Option<User>, StoreError> {
+        let mut memory = self.memory.lock().await;
+        if memory.users.iter().any(|user| user.username == username) {
+            return Ok(None);
+        }
+        let user = User {
+            id: memory.users.len() as UserId + 1,
+            username,
+            password_hash,
+        };
+        memory.users.push(user.clone());
+        Ok(Some(user))
+    }
+
+    async fn user_by_name(&self, username: String) -> Result<Option<User>, StoreError> {
+        let memory = self.memory.lock().await;
+        Ok(memory
+            .users
+            .iter()
+            .find(|user| user.username == username)
+            .cloned())
+    }
+
+    async fn create_session(
+        &self,
+        token_hash: String,
+        user_id: UserId,
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), StoreError> {
+        let mut memory = self.memory.lock().await;
+        memory.sessions.insert(token_hash, (user_id, expires_at));
+        Ok(())
+    }
+
+    async fn session_user(&self, token_hash: String) -> Result<Option<UserId>, StoreError> {
+        let memory = self.memory.lock().await;
+        Ok(memory
+            .sessions
+            .get(&token_hash)
+            .filter(|(_, expires_at)| *expires_at > Utc::now())
+            .map(|(user_id, _)| *user_id))
+    }
+
+    async fn delete_session(&self, token_hash: String) -> Result<(), StoreError> {
+        let mut memory = self.memory.lock().await;
+        memory.sessions.remove(&token_hash);
+        Ok(())
+    }
+}
@@ -0,0 +1,157 @@
+use std::rc::Rc;
+use std::time::Duration;
+
+use leptos::logging::error;
+use leptos::prelude::*;
+use leptos::task::spawn_local;
+
+use crate::models::{Node, NodeError, Storage};
+
+/// How long the tree has to stay unchanged before it is saved, so a burst of
+/// typing is written once.
+const SAVE_DELAY: Duration = Duration::from_secs(1);
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum SaveStatus {
+    Saved,
+    /// There are changes that will be saved once the tree is quiet.
+    Saving,
+    /// The last save failed, e.g. because the storage quota is exceeded.
+    /// The next change tries again.
+    Failed(NodeError),
+}
+
+/// Saves a tree whenever it changes, after `SAVE_DELAY` without further
+/// changes, and right away when the page is hidden or closed.
+///
+/// Saves run one at a time so that an older tree never overwrites a newer
+/// one. With an asynchronous storage, a save started on `beforeunload` may
+/// not finish; the one on `visibilitychange` usually comes first.
+#[derive(Clone, Copy)]
+pub struct Autosave {
+    node: Node,
+    storage: StoredValue<Rc<dyn Storage>, LocalStorage>,
+    key: StoredValue<String>,
+    pub status: RwSignal<SaveStatus>,
+    timer: StoredValue<Option<TimeoutHandle>>,
+    /// Counts the changes to the tree, so a save can tell whether it wrote
+    /// the latest one.
+    revision: StoredValue<u64>,
+    busy: StoredValue<bool>,
+    again: StoredValue<bool>,
+}
+
+impl Autosave {
+    pub fn start(node: Node, storage: Rc<dyn Storage>, key: &str) -> Self {
+        let autosave = Self {
+            node,
+            storage: StoredValue::new_local(storage),
+            key: StoredValue::new(key.to_string()),
+            status: RwSignal::new(SaveStatus::Saved),
+            timer: StoredValue::new(None),
+            revision: StoredValue::new(0),
+            busy: StoredValue::new(false),
+            again: StoredValue::new(false),
+        };
+
+        Effect::new(move |previous: Option<()>| {
+            // Reading the whole tree subscribes to every signal in it
+            node.to_data();
+            if previous.is_some() {
+                autosave.schedule();
+            }
+        });
+
+        let unload = window_event_listener(leptos::ev::beforeunload, move |_| autosave.flush());
+        let hidden = window_event_listener(leptos::ev::visibilitychange, move |_| {
+            if document().hidden() {
+                autosave.flush();
+            }
+        });
+        on_cleanup(move || {
+            unload.remove();
+            hidden.remove();
+            // Changes that were not taken before closing, e.g. when the user
+            // logs out, are saved as the editor goes away
+            if let Some(json_string) = autosave.take_unsaved() {
+                let storage = autosave.storage.get_value();
+                let key = autosave.key.get_value();
+                spawn_local(async move {
+                    if let Err(err) = storage.save(&key, json_string).await {
+                        error!("Failed to save {} before closing it: {}", key, err);
+                    }
+                });
+            }
+        });
+        autosave
+    }
+
+    fn schedule(self) {
+        self.revision.update_value(|revision| *revision += 1);
+        self.status.set(SaveStatus::Saving);
+        if let Some(timer) = self.timer.get_value() {
+            timer.clear();
+        }
+        let timer = set_timeout_with_handle(move || self.save_now(), SAVE_DELAY).ok();
+        self.timer.set_value(timer);
+    }
+
+    /// The tree as JSON if it has unsaved changes, which are then no longer
+    /// saved by this autosave. Used to save a document before closing it.
+    pub fn take_unsaved(self) -> Option<String> {
+        if self.status.get_untracked() == SaveStatus::Saved {
+            return None;
+        }
+        if let Some(timer) = self.timer.get_value() {
+            timer.clear();
+            self.timer.set_value(None);
+        }
+        self.status.set(SaveStatus::Saved);
+        Some(self.node.to_data().to_document().to_string())
+    }
+
+    /// Saves now if there are unsaved changes.
+    pub fn flush(self) {
+        if self.status.get_untracked() != SaveStatus::Saved {
+            self.save_now();
+        }
+    }
+
+    pub fn save_now(self) {
+        if let Some(timer) = self.timer.get_value() {
+            timer.clear();
+            self.timer.set_value(None);
+        }
+        if self.busy.get_value() {
+            self.again.set_value(true);
+            return;
+        }
+        self.busy.set_value(true);
+        self.status.set(SaveStatus::Saving);
+
+        let storage = self.storage.get_value();
+        let key = self.key.get_value();
+        let revision = self.revision.get_value();
+        spawn_local(async move {
+            let result = self.node.save(&*storage, &key).await;
+            if self.busy.try_set_value(false).is_some() {
+                // The document was closed in the meantime
+                return;
+            }
+            if self.again.get_value() {
+                self.again.set_value(false);
+                self.save_now();
+                return;
+            }
+            match result {
+                // A change made while saving is still to be saved by its timer
+                Ok(()) if self.revision.get_value() != revision => {}
+                Ok(()) => self.status.set(SaveStatus::Saved),
+                Err(err) => {
+                    error!("Autosave failed: {}", err);
+                    self.status.set(SaveStatus::Failed(err));
+                }
+            }
+        });
+    }
+}
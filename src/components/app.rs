@@ -1,33 +1,147 @@
+use std::rc::Rc;
+
 use leptos::logging::log;
 use leptos::prelude::*;
 use leptos::web_sys::console;
 use wasm_bindgen::JsValue;
 
-use crate::components::TreeView;
-use crate::models::Node;
-
-fn create_default_node() -> Node {
-    let child1 = Node::new(false, "bar1", Vec::new());
-    let child2 = Node::new(false, "bar2", Vec::new());
-    Node::new(false, "foo", vec![child1, child2])
-}
+use crate::autosave::Autosave;
+use crate::components::{
+    AccountMenu, Breadcrumbs, CompletedDisplay, DocumentList, DragState, FocusRequest,
+    ImportExport, SaveIndicator, Search, SearchBox, SyncIndicator, TreeView, Zoom,
+};
+use crate::documents::{Documents, LoadError, OpenDocument};
+use crate::models::{
+    EventLog, History, IndexedDbStore, LocalStore, MemoryStore, Storage, PRIMARY_KEY,
+};
+use crate::session::Session;
+use crate::sync::{SyncClient, SyncStatus};
+use crate::tabs::TabSync;
 
 #[component]
 pub fn App() -> impl IntoView {
     log!("starting...");
-    
-    // Try to load from localStorage, fall back to default if it fails
-    let node = match Node::load_from_local_storage("root") {
-        Ok(loaded_node) => {
-            log!("Loaded node from localStorage");
-            loaded_node
+
+    let storage: Rc<dyn Storage> = if IndexedDbStore::is_supported() {
+        Rc::new(IndexedDbStore::default())
+    } else if LocalStore::is_supported() {
+        Rc::new(LocalStore)
+    } else {
+        log!("No persistent storage available, changes are lost on reload");
+        Rc::new(MemoryStore::default())
+    };
+
+    // Documents are edited with or without a session; only syncing needs one
+    let session = RwSignal::new(Session::load());
+    provide_context(session);
+    let user_id = Memo::new(move |_| session.with(|session| session.as_ref().map(|s| s.user_id)));
+
+    let storage = StoredValue::new_local(storage);
+    // Each user has their own documents, so logging in or out opens those of
+    // the new user
+    move || {
+        let documents = Documents::start(storage.get_value(), user_id.get());
+        provide_context(documents);
+        view! {
+            <div class="app">
+                <DocumentList />
+                <main>
+                    {move || {
+                        documents
+                            .open
+                            .get()
+                            .map(|document| match &document.load_error {
+                                // Nothing is opened that could be saved over the stored tree
+                                Some(load_error @ LoadError { kept_as: None, .. }) => {
+                                    let load_error = load_error.clone();
+                                    view! { <LoadErrorMessage load_error /> }.into_any()
+                                }
+                                _ => view! { <Editor document /> }.into_any(),
+                            })
+                    }}
+                </main>
+            </div>
+        }
+    }
+}
+
+/// Why the stored tree of the open document could not be opened, and what
+/// became of it.
+#[component]
+fn LoadErrorMessage(load_error: LoadError) -> impl IntoView {
+    let message = match load_error.kept_as {
+        Some(kept_as) => format!(
+            "The saved document could not be opened ({}). It was kept under \"{}\" and the document was started over.",
+            load_error.error, kept_as
+        ),
+        None => format!(
+            "The saved document could not be opened ({}). It was left as it is; reload to try again.",
+            load_error.error
+        ),
+    };
+    view! { <p class="load-error">{message}</p> }
+}
+
+/// The toolbar and tree of a loaded document.
+#[component]
+fn Editor(document: OpenDocument) -> impl IntoView {
+    let node = document.node;
+    let log = EventLog::new(node);
+    provide_context(log);
+    // The server has a single document per user, so only the primary one is
+    // synced, and only while someone is logged in. The editor is made anew
+    // with the documents of whoever logs in next.
+    let session = expect_context::<RwSignal<Option<Session>>>()
+        .get_untracked()
+        .filter(|_| document.key == PRIMARY_KEY);
+    let upload = document.loaded;
+    // Other tabs with the same document open apply each other's edits, so
+    // their saves do not overwrite one another.
+    let documents = expect_context::<Documents>();
+    TabSync::start(log, &documents.stored_key(&document.key));
+    let autosave = Autosave::start(node, documents.storage(), &document.key);
+    documents.attach(autosave);
+    provide_context(autosave);
+    provide_context(FocusRequest::new());
+    provide_context(DragState::new());
+    let zoom = Zoom::start(log);
+    provide_context(zoom);
+    let view_root = zoom.view_root(log);
+    provide_context(Search::new(log));
+
+    let completed_display = RwSignal::new(CompletedDisplay::default());
+    provide_context::<Signal<CompletedDisplay>>(completed_display.into());
+
+    // Undo and redo work anywhere on the page, overriding the browser's own
+    // undo inside the editable spans.
+    let shortcuts = window_event_listener(leptos::ev::keydown, move |ev| {
+        if !(ev.ctrl_key() || ev.meta_key()) {
+            return;
+        }
+        match ev.key().to_lowercase().as_str() {
+            "z" if ev.shift_key() => {
+                ev.prevent_default();
+                log.redo();
+            }
+            "z" => {
+                ev.prevent_default();
+                log.undo();
+            }
+            "y" => {
+                ev.prevent_default();
+                log.redo();
+            }
+            _ => {}
         }
-        Err(err) => {
-            log!("Failed to load from localStorage: {}, using default", err);
-            create_default_node()
+    });
+    on_cleanup(move || shortcuts.remove());
+
+    let on_display_change = move |ev| {
+        if let Some(display) = CompletedDisplay::from_str(&event_target_value(&ev)) {
+            completed_display.set(display);
         }
     };
-    
+
     let log_node_json = move |_| {
         let json = node.to_json();
         // Format JSON with pretty printing (indent of 2 spaces)
@@ -35,18 +149,79 @@ pub fn App() -> impl IntoView {
         console::log_1(&JsValue::from_str(&json_string));
     };
 
-    let save_to_storage = move |_| match node.save_to_local_storage("root") {
-        Ok(_) => console::log_1(&JsValue::from_str(
-            "Node saved to localStorage with key 'root'",
-        )),
-        Err(err) => console::error_1(&JsValue::from_str(&format!("Error saving node: {}", err))),
+    let log_events_json = move |_| {
+        let json_string =
+            serde_json::to_string_pretty(&log.to_json()).unwrap_or_else(|_| log.to_json().to_string());
+        console::log_1(&JsValue::from_str(&json_string));
     };
 
+    let load_error = document.load_error.clone();
     view! {
         <div>
+            {load_error.map(|load_error| view! { <LoadErrorMessage load_error /> })}
+            <button
+                on:click=move |_| {
+                    log.undo();
+                }
+                disabled=move || !log.history.with(History::can_undo)
+            >
+                "Undo"
+            </button>
+            <button
+                on:click=move |_| {
+                    log.redo();
+                }
+                disabled=move || !log.history.with(History::can_redo)
+            >
+                "Redo"
+            </button>
             <button on:click=log_node_json>"Log Node JSON"</button>
-            <button on:click=save_to_storage>"Save to localStorage"</button>
-            <TreeView node />
+            <button on:click=log_events_json>"Log Events"</button>
+            <ImportExport />
+            <select on:change=on_display_change>
+                {CompletedDisplay::ALL
+                    .into_iter()
+                    .map(|display| {
+                        view! {
+                            <option
+                                value=display.as_str()
+                                selected=move || completed_display.get() == display
+                            >
+                                {display.label()}
+                            </option>
+                        }
+                    })
+                    .collect_view()}
+            </select>
+            <SearchBox />
+            <SaveIndicator />
+            {match session {
+                Some(session) => view! { <ServerSync log session upload /> }.into_any(),
+                None => view! { <SyncIndicator /> }.into_any(),
+            }}
+            <AccountMenu />
+            <Breadcrumbs />
+            {move || {
+                let node = node.find(view_root.get()).unwrap_or(node);
+                view! { <TreeView node /> }
+            }}
         </div>
     }
 }
+
+/// Syncs the tree of `log` as the user of `session`, uploading it on the
+/// first sync with `upload`, and shows how that goes. The session is
+/// forgotten once the server no longer accepts it.
+#[component]
+fn ServerSync(log: EventLog, session: Session, upload: bool) -> impl IntoView {
+    let sync = untrack(|| SyncClient::start(log, session.sync_config(), upload));
+    provide_context(sync);
+    let current = expect_context::<RwSignal<Option<Session>>>();
+    Effect::new(move |_| {
+        if sync.status.get() == SyncStatus::LoggedOut {
+            Session::forget();
+            current.set(None);
+        }
+    });
+    view! { <SyncIndicator /> }
+}
@@ -0,0 +1,139 @@
+use leptos::prelude::*;
+
+use crate::models::{EventLog, Node, NodeId};
+
+/// The node shown as the root of the view instead of the whole tree, if any.
+///
+/// It is kept in the URL hash, so that it survives a reload, can be
+/// bookmarked, and the browser's back button zooms back out.
+#[derive(Clone, Copy)]
+pub struct Zoom(pub RwSignal<Option<NodeId>>);
+
+/// The node a URL hash like `#<id>` zooms into.
+pub fn zoom_from_hash(hash: &str) -> Option<NodeId> {
+    NodeId::parse_str(hash.trim_start_matches('#')).ok()
+}
+
+fn current_hash() -> String {
+    window().location().hash().unwrap_or_default()
+}
+
+impl Zoom {
+    /// Zooms into the node in the URL hash if it is in the tree of `log`,
+    /// and keeps the two in step from then on.
+    pub fn start(log: EventLog) -> Self {
+        let initial = zoom_from_hash(&current_hash()).filter(|id| log.root.find(*id).is_some());
+        let zoom = Self(RwSignal::new(initial));
+
+        Effect::new(move |_| {
+            let zoomed = zoom.0.get();
+            if zoom_from_hash(&current_hash()) != zoomed {
+                let hash = zoomed.map(|id| id.to_string()).unwrap_or_default();
+                let _ = window().location().set_hash(&hash);
+            }
+        });
+
+        let handle = window_event_listener(leptos::ev::hashchange, move |_| {
+            let id = zoom_from_hash(&current_hash()).filter(|id| log.root.find(*id).is_some());
+            if zoom.0.get_untracked() != id {
+                zoom.0.set(id);
+            }
+        });
+        on_cleanup(move || handle.remove());
+
+        zoom
+    }
+
+    /// Makes the node with `id` the root of the view.
+    pub fn zoom_in(&self, id: NodeId) {
+        self.0.set(Some(id));
+    }
+
+    /// Shows the whole tree again.
+    pub fn reset(&self) {
+        self.0.set(None);
+    }
+
+    /// The node shown as the root of the view: the zoomed node, or the root
+    /// of the tree if there is none or it has been removed, also by sync or
+    /// another tab.
+    pub fn view_root(&self, log: EventLog) -> Memo<NodeId> {
+        let zoom = *self;
+        Memo::new(move |_| {
+            log.revision.track();
+            zoom.0
+                .get()
+                .and_then(|id| log.root.find(id))
+                .unwrap_or(log.root)
+                .id
+                .get_untracked()
+        })
+    }
+}
+
+/// The ancestors of the zoomed node, each of them a link that zooms out to
+/// it. Nothing is shown while the whole tree is.
+#[component]
+pub fn Breadcrumbs() -> impl IntoView {
+    let log = expect_context::<EventLog>();
+    let zoom = expect_context::<Zoom>();
+    let view_root = zoom.view_root(log);
+
+    let ancestors = move || log.root.ancestors(view_root.get()).unwrap_or_default();
+
+    let crumb = move |ancestor: Node| {
+        let id = ancestor.id.get_untracked();
+        let on_click = move |_| {
+            if id == log.root.id.get_untracked() {
+                zoom.reset();
+            } else {
+                zoom.zoom_in(id);
+            }
+        };
+        view! {
+            <span class="crumb" on:click=on_click>
+                {move || {
+                    let text = ancestor.text.get();
+                    if text.trim().is_empty() { "…".to_string() } else { text }
+                }}
+            </span>
+            " › "
+        }
+    };
+
+    view! {
+        <nav class="breadcrumbs" class:hidden=move || ancestors().is_empty()>
+            {move || ancestors().into_iter().map(crumb).collect_view()}
+        </nav>
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Event, NodeData};
+
+    #[test]
+    fn test_zoom_from_hash() {
+        let id = NodeData::next_id();
+        assert_eq!(zoom_from_hash(&format!("#{}", id)), Some(id));
+        assert_eq!(zoom_from_hash(&id.to_string()), Some(id));
+        assert_eq!(zoom_from_hash(""), None);
+        assert_eq!(zoom_from_hash("#"), None);
+        assert_eq!(zoom_from_hash("#not-a-node"), None);
+    }
+
+    #[test]
+    fn test_view_root_falls_back_when_zoomed_node_is_gone() {
+        let child = NodeData::new(false, "child", vec![]);
+        let child_id = child.id;
+        let log = EventLog::new(Node::from_data(&NodeData::new(true, "root", vec![child])));
+        let zoom = Zoom(RwSignal::new(Some(child_id)));
+        let view_root = zoom.view_root(log);
+        assert_eq!(view_root.get(), child_id);
+
+        // Removed elsewhere, e.g. on another device
+        assert!(log.apply(&Event::Removed { id: child_id }));
+        assert_eq!(view_root.get(), log.root.id.get_untracked());
+    }
+}
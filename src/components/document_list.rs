@@ -0,0 +1,106 @@
+use leptos::prelude::*;
+
+use crate::documents::Documents;
+
+/// Asks for a document name, returning `None` if the prompt was cancelled or
+/// left empty.
+fn prompt_name(message: &str, default: &str) -> Option<String> {
+    window()
+        .prompt_with_message_and_default(message, default)
+        .ok()
+        .flatten()
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+}
+
+/// The list of documents, to switch between them, with actions to create a
+/// document and to rename, duplicate or delete the open one.
+#[component]
+pub fn DocumentList() -> impl IntoView {
+    let documents = expect_context::<Documents>();
+
+    let active = move || {
+        documents
+            .index
+            .with(|index| index.as_ref().map(|index| index.active.clone()))
+    };
+    let active_name = move || {
+        documents.index.with_untracked(|index| {
+            index
+                .as_ref()
+                .and_then(|index| index.get(&index.active))
+                .map(|document| document.name.clone())
+        })
+    };
+
+    let new_click = move |_| {
+        if let Some(name) = prompt_name("Name of the new document", "") {
+            documents.create(name);
+        }
+    };
+    let rename_click = move |_| {
+        let (Some(key), Some(name)) = (active(), active_name()) else {
+            return;
+        };
+        if let Some(name) = prompt_name("New name of the document", &name) {
+            documents.rename(key, name);
+        }
+    };
+    let duplicate_click = move |_| {
+        if let Some(key) = active() {
+            documents.duplicate(key);
+        }
+    };
+    let delete_click = move |_| {
+        let (Some(key), Some(name)) = (active(), active_name()) else {
+            return;
+        };
+        let message = format!("Delete \"{}\" and everything in it?", name);
+        if window().confirm_with_message(&message).unwrap_or(false) {
+            documents.delete(key);
+        }
+    };
+    let can_delete = move || {
+        documents.index.with(|index| {
+            index
+                .as_ref()
+                .is_some_and(|index| index.can_delete(&index.active))
+        })
+    };
+
+    view! {
+        <nav class="documents">
+            <ul>
+                <For
+                    each=move || {
+                        documents
+                            .index
+                            .with(|index| index.as_ref().map(|index| index.documents.clone()))
+                            .unwrap_or_default()
+                    }
+                    key=|document| (document.key.clone(), document.name.clone())
+                    let:document
+                >
+                    <li
+                        class:active={
+                            let key = document.key.clone();
+                            move || active().as_ref() == Some(&key)
+                        }
+                        on:click={
+                            let key = document.key.clone();
+                            move |_| documents.select(key.clone())
+                        }
+                    >
+                        {document.name.clone()}
+                    </li>
+                </For>
+            </ul>
+            <button on:click=new_click>"New"</button>
+            <button on:click=rename_click>"Rename"</button>
+            <button on:click=duplicate_click>"Duplicate"</button>
+            <button on:click=delete_click disabled=move || !can_delete()>
+                "Delete"
+            </button>
+        </nav>
+    }
+}
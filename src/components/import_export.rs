@@ -0,0 +1,185 @@
+use std::time::Duration;
+
+use leptos::html::Input;
+use leptos::logging::error;
+use leptos::prelude::*;
+use leptos::task::spawn_local;
+use leptos::web_sys::{Blob, BlobPropertyBag, HtmlAnchorElement, Url};
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+
+use crate::components::Zoom;
+use crate::models::{EventLog, NodeData, NodeError};
+
+/// How long an exported file's object URL is kept for the download to start.
+const REVOKE_DELAY: Duration = Duration::from_secs(1);
+
+/// Lets the browser save `contents` as a file called `file_name`.
+fn download(file_name: &str, mime_type: &str, contents: &str) -> Result<(), JsValue> {
+    let parts = js_sys::Array::of1(&JsValue::from_str(contents));
+    let options = BlobPropertyBag::new();
+    options.set_type(mime_type);
+    let blob = Blob::new_with_str_sequence_and_options(&parts, &options)?;
+    let url = Url::create_object_url_with_blob(&blob)?;
+    let anchor: HtmlAnchorElement = document().create_element("a")?.unchecked_into();
+    anchor.set_href(&url);
+    anchor.set_download(file_name);
+    anchor.click();
+    // Firefox and Safari start the download after `click` returns, and fail
+    // it if the URL is gone by then
+    set_timeout(
+        move || {
+            let _ = Url::revoke_object_url(&url);
+        },
+        REVOKE_DELAY,
+    );
+    Ok(())
+}
+
+/// A file name for the subtree rooted at `node`, made from its text.
+fn file_name(node: &NodeData, extension: &str) -> String {
+    let stem: String = node
+        .text
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '-' })
+        .collect();
+    let stem = stem.trim_matches('-');
+    if stem.is_empty() {
+        format!("notes.{}", extension)
+    } else {
+        format!("{}.{}", stem, extension)
+    }
+}
+
+/// The file formats trees can be exported to and imported from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    /// A nested task list.
+    Markdown,
+    /// The interchange format of outliners.
+    Opml,
+}
+
+impl Format {
+    pub const ALL: [Format; 2] = [Format::Markdown, Format::Opml];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Format::Markdown => "Markdown",
+            Format::Opml => "OPML",
+        }
+    }
+
+    fn extension(&self) -> &'static str {
+        match self {
+            Format::Markdown => "md",
+            Format::Opml => "opml",
+        }
+    }
+
+    fn mime_type(&self) -> &'static str {
+        match self {
+            Format::Markdown => "text/markdown",
+            Format::Opml => "text/x-opml",
+        }
+    }
+
+    /// The files offered by the file picker.
+    fn accept(&self) -> &'static str {
+        match self {
+            Format::Markdown => ".md,.markdown,.txt,text/markdown,text/plain",
+            Format::Opml => ".opml,.xml,text/x-opml,application/xml,text/xml",
+        }
+    }
+
+    fn export(&self, node: &NodeData) -> String {
+        match self {
+            Format::Markdown => node.to_markdown(),
+            Format::Opml => node.to_opml(),
+        }
+    }
+
+    fn import(&self, text: &str) -> Result<Vec<NodeData>, NodeError> {
+        match self {
+            Format::Markdown => Ok(NodeData::from_markdown(text)),
+            Format::Opml => NodeData::from_opml(text),
+        }
+    }
+}
+
+/// Exports the subtree in view to a file in `format`, and imports the trees
+/// in such a file as children of the node in view.
+#[component]
+fn FormatButtons(format: Format) -> impl IntoView {
+    let log = expect_context::<EventLog>();
+    let view_root = expect_context::<Zoom>().view_root(log);
+    let file_input: NodeRef<Input> = NodeRef::new();
+
+    let export = move |_| {
+        let (file_name, contents) = log.with_tree(|tree| {
+            let node = tree.find(view_root.get_untracked()).unwrap_or(tree);
+            (file_name(node, format.extension()), format.export(node))
+        });
+        if let Err(err) = download(&file_name, format.mime_type(), &contents) {
+            error!("Failed to export {}: {:?}", format.label(), err);
+        }
+    };
+
+    let on_file_chosen = move |_| {
+        let Some(input) = file_input.get_untracked() else {
+            return;
+        };
+        let Some(file) = input.files().and_then(|files| files.get(0)) else {
+            return;
+        };
+        // Lets the same file be chosen again.
+        input.set_value("");
+        let parent = view_root.get_untracked();
+        spawn_local(async move {
+            let text = match JsFuture::from(file.text()).await {
+                Ok(text) => text.as_string().unwrap_or_default(),
+                Err(err) => {
+                    error!("Failed to read {}: {:?}", file.name(), err);
+                    return;
+                }
+            };
+            // The document may have been closed while the file was read.
+            if log.root.id.try_get_untracked().is_none() {
+                return;
+            }
+            match format.import(&text) {
+                Ok(nodes) => {
+                    if !log.insert_subtrees(parent, None, nodes) {
+                        error!("Nothing to import in {}", file.name());
+                    }
+                }
+                Err(err) => error!("Failed to import {}: {}", file.name(), err),
+            }
+        });
+    };
+
+    view! {
+        <button on:click=export>{format!("Export {}", format.label())}</button>
+        <button on:click=move |_| {
+            if let Some(input) = file_input.get_untracked() {
+                input.click();
+            }
+        }>{format!("Import {}", format.label())}</button>
+        <input
+            node_ref=file_input
+            type="file"
+            accept=format.accept()
+            hidden
+            on:change=on_file_chosen
+        />
+    }
+}
+
+/// Export and import buttons for each format.
+#[component]
+pub fn ImportExport() -> impl IntoView {
+    Format::ALL
+        .into_iter()
+        .map(|format| view! { <FormatButtons format /> })
+        .collect_view()
+}
@@ -0,0 +1,94 @@
+use leptos::prelude::*;
+use leptos::task::spawn_local;
+
+use crate::session::Session;
+
+/// Asks for a username and password to log in with, or to sign up as a new
+/// user, and sets the session in the context once the server accepts them.
+#[component]
+pub fn LoginForm() -> impl IntoView {
+    let session = expect_context::<RwSignal<Option<Session>>>();
+    let username = RwSignal::new(String::new());
+    let password = RwSignal::new(String::new());
+    let error = RwSignal::new(None::<String>);
+    let busy = RwSignal::new(false);
+
+    let submit = move |signup: bool| {
+        if busy.get_untracked() {
+            return;
+        }
+        busy.set(true);
+        error.set(None);
+        spawn_local(async move {
+            let result =
+                Session::log_in(&username.get_untracked(), &password.get_untracked(), signup).await;
+            busy.set(false);
+            match result {
+                Ok(new_session) => session.set(Some(new_session)),
+                Err(message) => error.set(Some(message)),
+            }
+        });
+    };
+
+    view! {
+        <form
+            class="login"
+            on:submit=move |ev| {
+                ev.prevent_default();
+                submit(false);
+            }
+        >
+            <input
+                type="text"
+                placeholder="Username"
+                autocomplete="username"
+                prop:value=move || username.get()
+                on:input=move |ev| username.set(event_target_value(&ev))
+            />
+            <input
+                type="password"
+                placeholder="Password"
+                autocomplete="current-password"
+                prop:value=move || password.get()
+                on:input=move |ev| password.set(event_target_value(&ev))
+            />
+            <div>
+                <button type="submit" disabled=move || busy.get()>
+                    "Log in"
+                </button>
+                <button type="button" disabled=move || busy.get() on:click=move |_| submit(true)>
+                    "Sign up"
+                </button>
+            </div>
+            {move || error.get().map(|message| view! { <span class="login-error">{message}</span> })}
+        </form>
+    }
+}
+
+/// The logged in user, with a button to log out, or the form to log in with
+/// while nobody is.
+#[component]
+pub fn AccountMenu() -> impl IntoView {
+    let session = expect_context::<RwSignal<Option<Session>>>();
+    let logged_in = Memo::new(move |_| session.with(Option::is_some));
+
+    let log_out = move |_| {
+        if let Some(current) = session.get_untracked() {
+            session.set(None);
+            spawn_local(current.log_out());
+        }
+    };
+
+    move || {
+        if !logged_in.get() {
+            return view! { <LoginForm /> }.into_any();
+        }
+        view! {
+            <span class="account">
+                {move || session.with(|session| session.as_ref().map(|s| s.username.clone()))}
+                <button on:click=log_out>"Log out"</button>
+            </span>
+        }
+        .into_any()
+    }
+}
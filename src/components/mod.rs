@@ -1,5 +1,19 @@
 mod app;
+mod breadcrumbs;
+mod document_list;
+mod import_export;
+mod login;
+mod save_indicator;
+mod search_box;
+mod sync_indicator;
 mod tree_view;
 
 pub use app::*;
+pub use breadcrumbs::*;
+pub use document_list::*;
+pub use import_export::*;
+pub use login::*;
+pub use save_indicator::*;
+pub use search_box::*;
+pub use sync_indicator::*;
 pub use tree_view::*;
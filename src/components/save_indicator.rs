@@ -0,0 +1,26 @@
+use leptos::prelude::*;
+
+use crate::autosave::{Autosave, SaveStatus};
+
+/// Shows whether the latest changes are saved locally, with a button to
+/// retry after a failure.
+#[component]
+pub fn SaveIndicator() -> impl IntoView {
+    let autosave = expect_context::<Autosave>();
+
+    let failed = move || matches!(autosave.status.get(), SaveStatus::Failed(_));
+    let label = move || match autosave.status.get() {
+        SaveStatus::Saved => "Saved".to_string(),
+        SaveStatus::Saving => "Saving…".to_string(),
+        SaveStatus::Failed(message) => format!("Save failed: {}", message),
+    };
+
+    view! {
+        <span class="save-status" class:save-failed=failed>
+            {label}
+            <Show when=failed>
+                <button on:click=move |_| autosave.save_now()>"Retry"</button>
+            </Show>
+        </span>
+    }
+}
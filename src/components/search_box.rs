@@ -0,0 +1,87 @@
+use std::collections::HashSet;
+
+use leptos::prelude::*;
+use leptos::web_sys::KeyboardEvent;
+
+use crate::models::{EventLog, NodeId};
+
+/// The text the tree is filtered by, and the nodes left to show.
+#[derive(Clone, Copy)]
+pub struct Search {
+    pub query: RwSignal<String>,
+    /// The nodes that match the query and their ancestors, or `None` while
+    /// nothing is searched for.
+    pub visible: Memo<Option<HashSet<NodeId>>>,
+    /// The shown nodes folded while searching. They are all expanded until
+    /// then, whatever their `is_open`.
+    pub collapsed: RwSignal<HashSet<NodeId>>,
+}
+
+impl Search {
+    pub fn new(log: EventLog) -> Self {
+        let query = RwSignal::new(String::new());
+        let visible = Memo::new(move |_| {
+            let query = query.get();
+            if query.trim().is_empty() {
+                return None;
+            }
+            log.revision.track();
+            Some(log.with_tree(|tree| tree.search(query.trim())))
+        });
+        Self {
+            query,
+            visible,
+            collapsed: RwSignal::new(HashSet::new()),
+        }
+    }
+
+    /// Searches for `query`, expanding all the nodes shown again.
+    pub fn set_query(&self, query: String) {
+        self.query.set(query);
+        self.collapsed.update(HashSet::clear);
+    }
+
+    /// Whether the node with `id` is expanded in the search results.
+    pub fn is_expanded(&self, id: NodeId) -> bool {
+        self.collapsed.with(|collapsed| !collapsed.contains(&id))
+    }
+
+    /// Folds or unfolds the node with `id` in the search results, leaving
+    /// its `is_open` as it is.
+    pub fn toggle(&self, id: NodeId) {
+        self.collapsed.update(|collapsed| {
+            if !collapsed.remove(&id) {
+                collapsed.insert(id);
+            }
+        });
+    }
+
+    /// The query to highlight in node texts.
+    pub fn highlighted(&self) -> String {
+        self.query.with(|query| query.trim().to_string())
+    }
+}
+
+/// Filters the tree to the nodes whose text contains what is typed, ignoring
+/// case. Escape clears the search.
+#[component]
+pub fn SearchBox() -> impl IntoView {
+    let search = expect_context::<Search>();
+
+    let on_keydown = move |ev: KeyboardEvent| {
+        if ev.key() == "Escape" {
+            search.set_query(String::new());
+        }
+    };
+
+    view! {
+        <input
+            type="search"
+            class="search"
+            placeholder="Search"
+            prop:value=move || search.query.get()
+            on:input=move |ev| search.set_query(event_target_value(&ev))
+            on:keydown=on_keydown
+        />
+    }
+}
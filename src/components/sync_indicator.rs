@@ -0,0 +1,38 @@
+use leptos::prelude::*;
+
+use crate::sync::{SyncClient, SyncStatus};
+
+/// Shows the state of the connection to the server and how many local
+/// changes are waiting to be pushed, with a button to sync right away.
+/// Documents without a `SyncClient` are only kept locally.
+#[component]
+pub fn SyncIndicator() -> impl IntoView {
+    let Some(sync) = use_context::<SyncClient>() else {
+        return view! { <span class="sync-status">"Local only"</span> }.into_any();
+    };
+
+    let label = move || {
+        let pending = sync.pending.with(Vec::len);
+        match sync.status.get() {
+            SyncStatus::Synced if pending == 0 => "Synced".to_string(),
+            SyncStatus::Synced | SyncStatus::Syncing => format!("Syncing {} changes…", pending),
+            SyncStatus::Offline => format!("Offline, {} changes pending", pending),
+            SyncStatus::Failed(message) => format!("Sync failed: {}", message),
+            SyncStatus::LoggedOut => format!("Logged out, {} changes pending", pending),
+        }
+    };
+    let class = move || match sync.status.get() {
+        SyncStatus::Synced | SyncStatus::Syncing => "sync-status",
+        SyncStatus::Offline | SyncStatus::Failed(_) | SyncStatus::LoggedOut => {
+            "sync-status sync-problem"
+        }
+    };
+
+    view! {
+        <span class=class>
+            {label}
+            <button on:click=move |_| sync.sync_now()>"Sync now"</button>
+        </span>
+    }
+    .into_any()
+}
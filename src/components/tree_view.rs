@@ -1,39 +1,249 @@
-use leptos::html::Span;
+use leptos::html::{Div, Span};
 use leptos::logging::log;
 use leptos::prelude::*;
 use leptos::web_sys::*;
 
-use crate::models::Node;
+use crate::components::{Search, Zoom};
+use crate::models::{
+    find_matches, now_millis, DropPosition, Event, EventLog, Node, NodeData, NodeId,
+};
+
+/// Asks the `TreeView` rendering a node to take keyboard focus once it is
+/// mounted, e.g. right after the node has been created or moved.
+#[derive(Clone, Copy)]
+pub struct FocusRequest(pub RwSignal<Option<NodeId>>);
+
+impl FocusRequest {
+    pub fn new() -> Self {
+        Self(RwSignal::new(None))
+    }
+
+    pub fn request(&self, id: NodeId) {
+        self.0.set(Some(id));
+    }
+}
+
+/// The node currently being dragged by its handle, if any.
+#[derive(Clone, Copy)]
+pub struct DragState(pub RwSignal<Option<NodeId>>);
+
+impl DragState {
+    pub fn new() -> Self {
+        Self(RwSignal::new(None))
+    }
+}
+
+/// Focuses `elem` and puts the caret after its last character.
+fn focus_end(elem: &HtmlElement) {
+    let _ = elem.focus();
+    let Ok(range) = document().create_range() else {
+        return;
+    };
+    if range.select_node_contents(elem).is_err() {
+        return;
+    }
+    range.collapse_with_to_start(false);
+    if let Ok(Some(selection)) = leptos::prelude::window().get_selection() {
+        let _ = selection.remove_all_ranges();
+        let _ = selection.add_range(&range);
+    }
+}
+
+/// The node text rendered `offset` places before (negative) or after
+/// (positive) `elem` in document order, i.e. among the visible nodes.
+fn neighbour_text(elem: &HtmlElement, offset: i32) -> Option<HtmlElement> {
+    let spans = document().query_selector_all("span.node-text").ok()?;
+    let elem: &leptos::web_sys::Node = elem.as_ref();
+    let index = (0..spans.length()).find(|&i| spans.item(i).as_ref() == Some(elem))?;
+    let neighbour = spans.item(index.checked_add_signed(offset)?)?;
+    wasm_bindgen::JsCast::dyn_into::<HtmlElement>(neighbour).ok()
+}
+
+/// Replaces the contents of `elem` with `text`, wrapping the byte ranges in
+/// `matches` in `<mark>` elements.
+fn highlight(elem: &HtmlElement, text: &str, matches: &[std::ops::Range<usize>]) {
+    let document = document();
+    elem.set_inner_text("");
+    let mut start = 0;
+    for range in matches {
+        let _ = elem.append_with_str_1(&text[start..range.start]);
+        if let Ok(mark) = document.create_element("mark") {
+            mark.set_text_content(Some(&text[range.clone()]));
+            let _ = elem.append_with_node_1(&mark);
+        }
+        start = range.end;
+    }
+    let _ = elem.append_with_str_1(&text[start..]);
+}
+
+/// How completed nodes are shown among their siblings.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CompletedDisplay {
+    #[default]
+    StrikeThrough,
+    Hidden,
+    Bottom,
+}
+
+impl CompletedDisplay {
+    pub const ALL: [CompletedDisplay; 3] = [
+        CompletedDisplay::StrikeThrough,
+        CompletedDisplay::Hidden,
+        CompletedDisplay::Bottom,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CompletedDisplay::StrikeThrough => "strike-through",
+            CompletedDisplay::Hidden => "hidden",
+            CompletedDisplay::Bottom => "bottom",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            CompletedDisplay::StrikeThrough => "Strike through completed",
+            CompletedDisplay::Hidden => "Hide completed",
+            CompletedDisplay::Bottom => "Move completed to bottom",
+        }
+    }
+
+    pub fn from_str(value: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|display| display.as_str() == value)
+    }
+}
+
+/// The children of `node` in the order they should be rendered.
+pub fn displayed_children(node: Node, display: CompletedDisplay) -> Vec<RwSignal<Node>> {
+    let children = node.children.get();
+    match display {
+        CompletedDisplay::StrikeThrough => children,
+        CompletedDisplay::Hidden => children
+            .into_iter()
+            .filter(|child| !child.get().done.get())
+            .collect(),
+        CompletedDisplay::Bottom => {
+            let (done, open): (Vec<_>, Vec<_>) =
+                children.into_iter().partition(|child| child.get().done.get());
+            open.into_iter().chain(done).collect()
+        }
+    }
+}
 
 #[component]
 pub fn TreeView(node: Node, #[prop(optional)] on_remove: Option<Callback<Node>>) -> impl IntoView {
+    let log = expect_context::<EventLog>();
+    let focus = expect_context::<FocusRequest>();
+    let drag = expect_context::<DragState>();
+    let zoom = use_context::<Zoom>();
+    let search = use_context::<Search>();
+    let display = use_context::<Signal<CompletedDisplay>>().unwrap_or_default();
     let is_open = node.is_open;
-    let set_is_open = node.is_open.write_only();
+    let done = node.done;
     let text = node.text.read_only();
-    let set_text = node.text.write_only();
+
+    // While searching, only matching nodes and their ancestors are shown, and
+    // they are expanded and folded without changing `is_open`.
+    let searching = move || search.filter(|search| search.visible.with(Option::is_some));
+    let expanded = move || match searching() {
+        Some(search) => search.is_expanded(node.id.get()),
+        None => is_open.get(),
+    };
+    let children = move || {
+        let children = displayed_children(node, display.get());
+        let Some(search) = search else {
+            return children;
+        };
+        search.visible.with(|visible| match visible {
+            Some(visible) => children
+                .into_iter()
+                .filter(|child| visible.contains(&child.get().id()))
+                .collect(),
+            None => children,
+        })
+    };
+
+    // The zoomed node stands in for the root: it has no siblings in view and
+    // cannot be removed from within it.
+    let is_view_root = move |id: NodeId| {
+        log.with_tree(|tree| tree.find_parent(id).is_none())
+            || zoom.is_some_and(|zoom| zoom.0.get_untracked() == Some(id))
+    };
+
+    // Where a node added after the one with `id` goes. The root of the view
+    // has no siblings, so it gets a first child instead.
+    let next_slot = move |id: NodeId| {
+        if is_view_root(id) {
+            return (id, 0);
+        }
+        log.with_tree(|tree| match tree.find_parent(id) {
+            Some(parent) => (parent.id, parent.child_index(id).unwrap_or(0) + 1),
+            None => (id, 0),
+        })
+    };
+
+    let bullet_click = move |_ev: MouseEvent| {
+        if let Some(zoom) = zoom {
+            zoom.zoom_in(node.id.get_untracked());
+        }
+    };
 
     let fold_click = move |_ev: MouseEvent| {
-        set_is_open.update(|open| *open = !*open);
+        let searching = search.filter(|search| search.visible.with_untracked(Option::is_some));
+        if let Some(search) = searching {
+            search.toggle(node.id.get_untracked());
+            return;
+        }
+        log.dispatch(Event::Folded {
+            id: node.id.get_untracked(),
+            open: !is_open.get_untracked(),
+        });
     };
 
-    let on_input = move |ev: Event| {
+    let on_input = move |ev: leptos::web_sys::Event| {
         if let Some(target) = ev.target() {
             if let Ok(elem) = wasm_bindgen::JsCast::dyn_into::<HtmlElement>(target) {
                 let new_text = elem.inner_text().to_string();
                 log!("onInput fired with text `{}`", &new_text);
-                set_text.update(|c| *c = new_text);
+                log.dispatch(Event::Edited {
+                    id: node.id.get_untracked(),
+                    text: new_text,
+                    from: None,
+                });
             }
         }
     };
 
+    let on_done_change = move |ev: leptos::web_sys::Event| {
+        let id = node.id.get_untracked();
+        if event_target_checked(&ev) {
+            log.dispatch(Event::MarkedAsDone {
+                id,
+                at: Some(now_millis()),
+            });
+        } else {
+            log.dispatch(Event::MarkedAsUndone { id });
+        }
+    };
+
     let add_empty_node = move |_ev: MouseEvent| {
-        let empty_node = Node::new(false, "", vec![]);
-        node.prepend_child(empty_node);
-        node.is_open.update(|o| *o = true);
+        let id = node.id.get_untracked();
+        log.transaction(|| {
+            log.dispatch(Event::Added {
+                id: NodeData::next_id(),
+                parent: Some(id),
+                index: 0,
+                after: None,
+                text: String::new(),
+            });
+            log.unfold(id);
+        });
     };
 
     let on_remove_cb = Callback::new(move |n: Node| {
-        node.remove_child(n.id.get_untracked());
+        log.dispatch(Event::Removed {
+            id: n.id.get_untracked(),
+        });
     });
 
     let remove_click = move |_ev: MouseEvent| {
@@ -42,7 +252,192 @@ pub fn TreeView(node: Node, #[prop(optional)] on_remove: Option<Callback<Node>>)
         }
     };
 
+    let move_click = move |move_node: fn(&EventLog, NodeId) -> bool| {
+        move |_ev: MouseEvent| {
+            move_node(&log, node.id.get_untracked());
+        }
+    };
+
     let span_ref: NodeRef<Span> = NodeRef::new();
+    let row_ref: NodeRef<Div> = NodeRef::new();
+    let drop_position = RwSignal::new(None::<DropPosition>);
+
+    let on_drag_start = move |ev: DragEvent| {
+        if let Some(data) = ev.data_transfer() {
+            data.set_effect_allowed("move");
+            // Firefox only starts a drag if some data is set.
+            let _ = data.set_data("text/plain", &text.get_untracked());
+            if let Some(row) = row_ref.get_untracked() {
+                data.set_drag_image(&row, 0, 0);
+            }
+        }
+        drag.0.set(Some(node.id.get_untracked()));
+    };
+
+    let on_drag_end = move |_ev: DragEvent| {
+        drag.0.set(None);
+    };
+
+    // Where the dragged node would land relative to this one, based on which
+    // part of the row the pointer is over, if dropping it there is allowed.
+    let hovered_position = move |ev: &DragEvent| {
+        let dragged = drag.0.get_untracked()?;
+        let rect = row_ref.get_untracked()?.get_bounding_client_rect();
+        let fraction = (f64::from(ev.client_y()) - rect.top()) / rect.height();
+        let position = if fraction < 0.25 {
+            DropPosition::Before
+        } else if fraction > 0.75 {
+            DropPosition::After
+        } else {
+            DropPosition::Inside
+        };
+        log.with_tree(|tree| tree.drop_target(dragged, node.id.get_untracked(), position))
+            .map(|_| position)
+    };
+
+    let on_drag_over = move |ev: DragEvent| {
+        let position = hovered_position(&ev);
+        if position.is_some() {
+            // Accepting the drop requires cancelling `dragover`.
+            ev.prevent_default();
+        }
+        if drop_position.get_untracked() != position {
+            drop_position.set(position);
+        }
+    };
+
+    let on_drag_leave = move |_ev: DragEvent| {
+        drop_position.set(None);
+    };
+
+    let on_drop = move |ev: DragEvent| {
+        ev.prevent_default();
+        drop_position.set(None);
+        if let (Some(dragged), Some(position)) = (drag.0.get_untracked(), hovered_position(&ev)) {
+            let target =
+                log.with_tree(|tree| tree.drop_target(dragged, node.id.get_untracked(), position));
+            log.move_to(dragged, target);
+        }
+        drag.0.set(None);
+    };
+
+    let on_keydown = move |ev: KeyboardEvent| {
+        let id = node.id.get_untracked();
+        match ev.key().as_str() {
+            "Enter" if !ev.shift_key() => {
+                ev.prevent_default();
+                let (parent, index) = next_slot(id);
+                let new_id = NodeData::next_id();
+                let added = log.transaction(|| {
+                    let added = log.dispatch(Event::Added {
+                        id: new_id,
+                        parent: Some(parent),
+                        index,
+                        after: None,
+                        text: String::new(),
+                    });
+                    if added && parent == id {
+                        log.unfold(id);
+                    }
+                    added
+                });
+                if added {
+                    focus.request(new_id);
+                }
+            }
+            "Tab" => {
+                ev.prevent_default();
+                let moved = if ev.shift_key() {
+                    log.outdent(id)
+                } else {
+                    log.indent(id)
+                };
+                if moved {
+                    focus.request(id);
+                }
+            }
+            "ArrowUp" | "ArrowDown" if ev.alt_key() => {
+                ev.prevent_default();
+                let offset = if ev.key() == "ArrowUp" { -1 } else { 1 };
+                if log.reorder(id, offset) {
+                    focus.request(id);
+                }
+            }
+            "Backspace" => {
+                let is_empty = text.get_untracked().trim().is_empty();
+                let is_leaf = node.children.with_untracked(Vec::is_empty);
+                if !is_empty || !is_leaf || is_view_root(id) {
+                    return;
+                }
+                ev.prevent_default();
+                let neighbour = span_ref.get_untracked().and_then(|span| {
+                    neighbour_text(&span, -1).or_else(|| neighbour_text(&span, 1))
+                });
+                if log.dispatch(Event::Removed { id }) {
+                    if let Some(neighbour) = neighbour {
+                        focus_end(&neighbour);
+                    }
+                }
+            }
+            "ArrowUp" | "ArrowDown" => {
+                let offset = if ev.key() == "ArrowUp" { -1 } else { 1 };
+                if let Some(neighbour) = span_ref
+                    .get_untracked()
+                    .and_then(|span| neighbour_text(&span, offset))
+                {
+                    ev.prevent_default();
+                    focus_end(&neighbour);
+                }
+            }
+            _ => {}
+        }
+    };
+
+    // Pasting several lines adds them as nodes after this one, nested by
+    // their indentation, instead of putting them all into its text.
+    let on_paste = move |ev: leptos::web_sys::Event| {
+        let ev: ClipboardEvent = wasm_bindgen::JsCast::unchecked_into(ev);
+        let Some(pasted) = ev
+            .clipboard_data()
+            .and_then(|data| data.get_data("text/plain").ok())
+        else {
+            return;
+        };
+        if !pasted.trim().contains('\n') {
+            return;
+        }
+        ev.prevent_default();
+        let nodes = NodeData::from_indented_text(&pasted);
+        let Some(first) = nodes.first().map(|node| node.id) else {
+            return;
+        };
+        let (parent, index) = next_slot(node.id.get_untracked());
+        if log.insert_subtrees(parent, Some(index), nodes) {
+            focus.request(first);
+        }
+    };
+
+    // Copying without a selection copies this node and its subtree as
+    // indented text.
+    let on_copy = move |ev: leptos::web_sys::Event| {
+        let ev: ClipboardEvent = wasm_bindgen::JsCast::unchecked_into(ev);
+        let collapsed = document()
+            .get_selection()
+            .ok()
+            .flatten()
+            .is_none_or(|selection| selection.is_collapsed());
+        if !collapsed {
+            return;
+        }
+        if let Some(data) = ev.clipboard_data() {
+            if data
+                .set_data("text/plain", &node.to_data().to_indented_text())
+                .is_ok()
+            {
+                ev.prevent_default();
+            }
+        }
+    };
 
     let has_focus = move || {
         if let Some(span_el) = span_ref.get() {
@@ -54,29 +449,118 @@ pub fn TreeView(node: Node, #[prop(optional)] on_remove: Option<Callback<Node>>)
     };
 
     Effect::new(move |_| {
+        let text = text.get();
+        let query = search
+            .map(|search| search.highlighted())
+            .unwrap_or_default();
         if let Some(span) = span_ref.get() {
-            if !has_focus() {
-                span.set_inner_text(&text.get());
+            let matches = find_matches(&text, &query);
+            // The focused span is not redrawn, as that would move the caret.
+            let focused = has_focus();
+            if !matches.is_empty() && !focused {
+                highlight(&span, &text, &matches);
+            } else if span.inner_text() != text || (span.child_element_count() > 0 && !focused) {
+                // Typing has already put the text into the span, but an undo
+                // while the span has focus has not.
+                span.set_inner_text(&text);
+                if focused {
+                    focus_end(&span);
+                }
             }
         };
     });
 
+    Effect::new(move |_| {
+        if focus.0.get() == Some(node.id.get_untracked()) {
+            if let Some(span) = span_ref.get() {
+                focus_end(&span);
+                focus.0.set(None);
+            }
+        }
+    });
+
     view! {
         <div>
-            <span class="carret" on:click=fold_click>
-                {move || if is_open.get() {"⌄ "} else {"〉 "}}
-            </span>
-            <span node_ref=span_ref on:input=on_input class="node-text" contenteditable="true"></span>
-            <button class="action" on:click=remove_click>
-                "-"
-            </button>
-            <button class="action" on:click=add_empty_node>
-                "+"
-            </button>
-            <Show when=move || is_open.get() && !node.children.get().is_empty()>
+            <div
+                node_ref=row_ref
+                class="row"
+                class:drop-before=move || drop_position.get() == Some(DropPosition::Before)
+                class:drop-after=move || drop_position.get() == Some(DropPosition::After)
+                class:drop-inside=move || drop_position.get() == Some(DropPosition::Inside)
+                on:dragover=on_drag_over
+                on:dragleave=on_drag_leave
+                on:drop=on_drop
+            >
+                <span
+                    class="drag-handle"
+                    title="Drag to move"
+                    draggable="true"
+                    on:dragstart=on_drag_start
+                    on:dragend=on_drag_end
+                >
+                    "⠿"
+                </span>
+                <span class="carret" on:click=fold_click>
+                    {move || if expanded() {"⌄ "} else {"〉 "}}
+                </span>
+                <span class="bullet" title="Zoom in" on:click=bullet_click>
+                    "•"
+                </span>
+                <input
+                    type="checkbox"
+                    class="done-toggle"
+                    prop:checked=move || done.get()
+                    on:change=on_done_change
+                />
+                <span
+                    node_ref=span_ref
+                    on:input=on_input
+                    on:keydown=on_keydown
+                    on:paste=on_paste
+                    on:copy=on_copy
+                    class="node-text"
+                    class:done=move || done.get()
+                    contenteditable="true"
+                ></span>
+                <button class="action" on:click=remove_click>
+                    "-"
+                </button>
+                <button class="action" on:click=add_empty_node>
+                    "+"
+                </button>
+                <button
+                    class="action"
+                    title="Indent"
+                    on:click=move_click(EventLog::indent)
+                >
+                    "→"
+                </button>
+                <button
+                    class="action"
+                    title="Outdent"
+                    on:click=move_click(EventLog::outdent)
+                >
+                    "←"
+                </button>
+                <button
+                    class="action"
+                    title="Move down"
+                    on:click=move_click(|log, id| log.reorder(id, 1))
+                >
+                    "↓"
+                </button>
+                <button
+                    class="action"
+                    title="Move up"
+                    on:click=move_click(|log, id| log.reorder(id, -1))
+                >
+                    "↑"
+                </button>
+            </div>
+            <Show when=move || expanded() && !children().is_empty()>
                 <div class="details">
                     <For
-                        each=move || node.children.get()
+                        each=children
                         key=|child| child.get().id()
                         let:child
                     >
@@ -90,18 +574,24 @@ pub fn TreeView(node: Node, #[prop(optional)] on_remove: Option<Callback<Node>>)
 }
 
 #[cfg(test)]
-#[allow(dead_code)]
 mod tests {
     use super::*;
     use wasm_bindgen_test::*;
 
     wasm_bindgen_test_configure!(run_in_browser);
 
+    /// The projection of a new node with the given children.
+    fn new_node(is_open: bool, text: &str, children: Vec<Node>) -> Node {
+        let children = children.into_iter().map(Node::to_data).collect();
+        Node::from_data(&NodeData::new(is_open, text, children))
+    }
+
+    #[cfg(target_arch = "wasm32")]
     #[wasm_bindgen_test]
     fn test_tree_view_initial_state() {
         // Create a simple node tree
-        let leaf = Node::new(false, "Leaf Node", vec![]);
-        let root = Node::new(true, "Root Node", vec![leaf]);
+        let leaf = new_node(false, "Leaf Node", vec![]);
+        let root = new_node(true, "Root Node", vec![leaf]);
 
         // Test initial state values
         assert_eq!(root.text.get(), "Root Node");
@@ -113,10 +603,11 @@ mod tests {
         assert_eq!(child.is_open.get(), false);
     }
 
+    #[cfg(target_arch = "wasm32")]
     #[wasm_bindgen_test]
     fn test_tree_view_toggle() {
         // Create node with initial open state
-        let node = Node::new(true, "Test Node", vec![]);
+        let node = new_node(true, "Test Node", vec![]);
 
         // Get writable signal
         let set_is_open = node.is_open.write_only();
@@ -130,10 +621,11 @@ mod tests {
         assert_eq!(node.is_open.get(), true);
     }
 
+    #[cfg(target_arch = "wasm32")]
     #[wasm_bindgen_test]
     fn test_tree_view_text_update() {
         // Create a node for testing
-        let node = Node::new(false, "Initial Text", vec![]);
+        let node = new_node(false, "Initial Text", vec![]);
 
         // Get writable signal
         let set_text = node.text.write_only();
@@ -146,12 +638,13 @@ mod tests {
         assert_eq!(node.text.get(), new_text);
     }
 
+    #[cfg(target_arch = "wasm32")]
     #[wasm_bindgen_test]
     fn test_tree_view_children_access() {
         // Create a node with children
-        let child1 = Node::new(false, "Child 1", vec![]);
-        let child2 = Node::new(false, "Child 2", vec![]);
-        let parent = Node::new(false, "Parent Node", vec![child1, child2]);
+        let child1 = new_node(false, "Child 1", vec![]);
+        let child2 = new_node(false, "Child 2", vec![]);
+        let parent = new_node(false, "Parent Node", vec![child1, child2]);
 
         // Test children count
         assert_eq!(parent.children.get().len(), 2);
@@ -164,12 +657,13 @@ mod tests {
         assert_eq!(second_child.text.get(), "Child 2");
     }
 
+    #[cfg(target_arch = "wasm32")]
     #[wasm_bindgen_test]
     fn test_tree_view_nested_children() {
         // Create a deeply nested tree structure
-        let grandchild = Node::new(false, "Grandchild", vec![]);
-        let child = Node::new(true, "Child", vec![grandchild]);
-        let parent = Node::new(true, "Parent", vec![child]);
+        let grandchild = new_node(false, "Grandchild", vec![]);
+        let child = new_node(true, "Child", vec![grandchild]);
+        let parent = new_node(true, "Parent", vec![child]);
 
         // Access nested children
         let child_node = parent.children.get().first().unwrap().get();
@@ -179,10 +673,11 @@ mod tests {
         assert_eq!(grandchild_node.text.get(), "Grandchild");
     }
 
+    #[cfg(target_arch = "wasm32")]
     #[wasm_bindgen_test]
     fn test_tree_view_empty_children() {
         // Test node with no children
-        let node = Node::new(true, "Empty Node", vec![]);
+        let node = new_node(true, "Empty Node", vec![]);
 
         // Verify children count
         assert_eq!(node.children.get().len(), 0);
@@ -191,13 +686,45 @@ mod tests {
         assert!(node.children.get().is_empty());
     }
 
+    #[test]
+    fn test_displayed_children() {
+        let open1 = new_node(false, "Open 1", vec![]);
+        let done = new_node(false, "Done", vec![]);
+        let open2 = new_node(false, "Open 2", vec![]);
+        done.done.set(true);
+        let parent = new_node(true, "Parent", vec![open1, done, open2]);
+
+        let texts = |display| {
+            displayed_children(parent, display)
+                .iter()
+                .map(|child| child.get().text.get())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            texts(CompletedDisplay::StrikeThrough),
+            ["Open 1", "Done", "Open 2"]
+        );
+        assert_eq!(texts(CompletedDisplay::Hidden), ["Open 1", "Open 2"]);
+        assert_eq!(texts(CompletedDisplay::Bottom), ["Open 1", "Open 2", "Done"]);
+    }
+
+    #[test]
+    fn test_completed_display_from_str() {
+        for display in CompletedDisplay::ALL {
+            assert_eq!(CompletedDisplay::from_str(display.as_str()), Some(display));
+        }
+        assert_eq!(CompletedDisplay::from_str("sideways"), None);
+    }
+
+    #[cfg(target_arch = "wasm32")]
     #[wasm_bindgen_test]
     fn test_tree_view_add_child() {
         // Create a parent node with no children initially
-        let parent = Node::new(true, "Parent", vec![]);
+        let parent = new_node(true, "Parent", vec![]);
 
         // Create a child node
-        let child = Node::new(false, "New Child", vec![]);
+        let child = new_node(false, "New Child", vec![]);
 
         // Add child to parent
         let mut children = parent.children.get();
@@ -0,0 +1,274 @@
+use std::rc::Rc;
+
+use leptos::logging::{error, log};
+use leptos::prelude::*;
+use leptos::task::spawn_local;
+
+use crate::autosave::Autosave;
+use crate::models::{
+    claim, keep_corrupt, migrate, DocumentIndex, LocalStore, Node, NodeData, NodeError, Storage,
+    UserStore, INDEX_KEY, PRIMARY_KEY,
+};
+
+fn create_default_node() -> Node {
+    let child1 = NodeData::new(false, "bar1", Vec::new());
+    let child2 = NodeData::new(false, "bar2", Vec::new());
+    Node::from_data(&NodeData::new(false, "foo", vec![child1, child2]))
+}
+
+/// The document being edited.
+#[derive(Clone)]
+pub struct OpenDocument {
+    pub key: String,
+    pub node: Node,
+    /// Whether the tree was found in storage rather than made up.
+    pub loaded: bool,
+    /// Why the stored tree could not be opened, if it could not.
+    pub load_error: Option<LoadError>,
+}
+
+/// A stored tree that could not be read or parsed. The document is opened
+/// empty only once the stored value has been copied to `kept_as`, so that
+/// saving the empty tree does not lose it.
+#[derive(Clone, Debug)]
+pub struct LoadError {
+    pub error: NodeError,
+    pub kept_as: Option<String>,
+}
+
+/// The document index and the open document of a user, or of nobody while
+/// logged out, kept in storage.
+///
+/// Changes to the index are saved right away. Before another document is
+/// opened, unsaved changes of the open one are taken from its autosave and
+/// saved first.
+#[derive(Clone, Copy)]
+pub struct Documents {
+    storage: StoredValue<UserStore, LocalStorage>,
+    pub index: RwSignal<Option<DocumentIndex>>,
+    pub open: RwSignal<Option<OpenDocument>>,
+    autosave: StoredValue<Option<Autosave>>,
+}
+
+impl Documents {
+    /// Loads the index of `user_id`, moving the tree from localStorage over
+    /// to `storage` if an earlier version saved it there, and opens the
+    /// active document. A user without documents yet takes over the ones
+    /// made without a session.
+    pub fn start(storage: Rc<dyn Storage>, user_id: Option<i64>) -> Self {
+        let store = UserStore::new(storage.clone(), user_id);
+        let documents = Self {
+            storage: StoredValue::new_local(store.clone()),
+            index: RwSignal::new(None),
+            open: RwSignal::new(None),
+            autosave: StoredValue::new(None),
+        };
+
+        spawn_local(async move {
+            match migrate(&LocalStore, &*storage, PRIMARY_KEY).await {
+                Ok(true) => log!("Moved node from localStorage"),
+                Ok(false) => {}
+                Err(err) => log!("Failed to move node from localStorage: {}", err),
+            }
+            if user_id.is_some() {
+                match claim(&*storage, &store).await {
+                    Ok(true) => log!("Moved the documents made without a session"),
+                    Ok(false) => {}
+                    Err(err) => error!(
+                        "Failed to move the documents made without a session: {}",
+                        err
+                    ),
+                }
+            }
+            let index = match DocumentIndex::load(&store).await {
+                Ok(index) => index,
+                Err(err) => {
+                    error!("Failed to load document index: {}, using default", err);
+                    // The default index is saved over it on the next change
+                    match keep_corrupt(&store, INDEX_KEY).await {
+                        Ok(kept) => log!("Kept the document index as {:?}", kept),
+                        Err(err) => error!("Failed to keep the document index: {}", err),
+                    }
+                    DocumentIndex::default()
+                }
+            };
+            documents.index.set(Some(index));
+            documents.open_active().await;
+        });
+        documents
+    }
+
+    /// Registers the autosave of the open document.
+    pub fn attach(self, autosave: Autosave) {
+        self.autosave.set_value(Some(autosave));
+        on_cleanup(move || {
+            self.autosave.set_value(None);
+        });
+    }
+
+    /// Where the trees of the documents are saved.
+    pub fn storage(self) -> Rc<dyn Storage> {
+        Rc::new(self.storage.get_value())
+    }
+
+    /// The key the document with `key` is saved under, which is also unique
+    /// across users.
+    pub fn stored_key(self, key: &str) -> String {
+        self.storage.with_value(|storage| storage.key(key))
+    }
+
+    async fn save_index(self) {
+        let Some(index) = self.index.get_untracked() else {
+            return;
+        };
+        if let Err(err) = index.save(&*self.storage()).await {
+            error!("Failed to save document index: {}", err);
+        }
+    }
+
+    /// Closes the open document, saving unsaved changes unless `discard`.
+    async fn close(self, discard: bool) {
+        let unsaved = self
+            .autosave
+            .get_value()
+            .and_then(|autosave| autosave.take_unsaved());
+        let closed = self.open.get_untracked();
+        self.open.set(None);
+        if let (Some(json_string), Some(closed), false) = (unsaved, closed, discard) {
+            if let Err(err) = self.storage().save(&closed.key, json_string).await {
+                error!("Failed to save {} before closing it: {}", closed.key, err);
+            }
+        }
+    }
+
+    async fn open_active(self) {
+        let Some(index) = self.index.get_untracked() else {
+            return;
+        };
+        let key = index.active.clone();
+        let (node, loaded, load_error) = match Node::load(&*self.storage(), &key).await {
+            Ok(node) => {
+                log!("Loaded node from storage");
+                (node, true, None)
+            }
+            Err(err) => {
+                let load_error = match err {
+                    NodeError::KeyMissing(_) => None,
+                    error => {
+                        error!("Failed to load {}: {}", key, error);
+                        let kept_as =
+                            keep_corrupt(&*self.storage(), &key)
+                                .await
+                                .unwrap_or_else(|err| {
+                                    error!("Failed to keep {}: {}", key, err);
+                                    None
+                                });
+                        Some(LoadError { error, kept_as })
+                    }
+                };
+                log!("Using a default tree for {}", key);
+                let node = if key == PRIMARY_KEY {
+                    create_default_node()
+                } else {
+                    let name = index.get(&key).map(|document| document.name.as_str());
+                    Node::from_data(&NodeData::new(true, name.unwrap_or_default(), vec![]))
+                };
+                (node, false, load_error)
+            }
+        };
+        self.open.set(Some(OpenDocument {
+            key,
+            node,
+            loaded,
+            load_error,
+        }));
+    }
+
+    /// Applies `change` to the index and opens the then active document,
+    /// closing the open one if it changed.
+    fn switch(self, change: impl FnOnce(&mut DocumentIndex) + 'static) {
+        spawn_local(async move {
+            let before = self
+                .open
+                .with_untracked(|open| open.as_ref().map(|open| open.key.clone()));
+            let mut index = self.index.get_untracked().unwrap_or_default();
+            change(&mut index);
+            let after = index.active.clone();
+            let deleted = before.as_ref().is_some_and(|key| index.get(key).is_none());
+            self.index.set(Some(index));
+            self.save_index().await;
+
+            if before.as_deref() != Some(after.as_str()) {
+                self.close(deleted).await;
+                self.open_active().await;
+            }
+        });
+    }
+
+    pub fn select(self, key: String) {
+        self.switch(move |index| {
+            index.select(&key);
+        });
+    }
+
+    pub fn create(self, name: String) {
+        self.switch(move |index| {
+            index.create(&name);
+        });
+    }
+
+    pub fn rename(self, key: String, name: String) {
+        self.index.update(|index| {
+            if let Some(index) = index {
+                index.rename(&key, &name);
+            }
+        });
+        spawn_local(self.save_index());
+    }
+
+    /// Copies the document with `key`, including unsaved changes if it is
+    /// the open one, and opens the copy.
+    pub fn duplicate(self, key: String) {
+        spawn_local(async move {
+            let open = self.open.get_untracked().filter(|open| open.key == key);
+            let json_string = match open {
+                Some(open) => Some(open.node.to_data().to_document().to_string()),
+                None => self.storage().load(&key).await.ok().flatten(),
+            };
+            let mut index = self.index.get_untracked().unwrap_or_default();
+            let Some(copy) = index.duplicate(&key) else {
+                return;
+            };
+            if let Some(json_string) = json_string {
+                if let Err(err) = self.storage().save(&copy, json_string).await {
+                    error!("Failed to copy {}: {}", key, err);
+                    return;
+                }
+            }
+            self.switch(move |updated| *updated = index);
+        });
+    }
+
+    /// Removes the document with `key` and its tree. The last and the
+    /// primary document are never removed.
+    pub fn delete(self, key: String) {
+        let removable = self
+            .index
+            .with_untracked(|index| index.as_ref().is_some_and(|index| index.can_delete(&key)));
+        if !removable {
+            return;
+        }
+        let storage = self.storage();
+        self.switch({
+            let key = key.clone();
+            move |index| {
+                index.delete(&key);
+            }
+        });
+        spawn_local(async move {
+            if let Err(err) = storage.remove(&key).await {
+                error!("Failed to remove {}: {}", key, err);
+            }
+        });
+    }
+}
@@ -1,5 +1,10 @@
+mod autosave;
 mod components;
+mod documents;
 mod models;
+mod session;
+mod sync;
+mod tabs;
 
 use leptos::prelude::*;
 use components::App;
@@ -0,0 +1,301 @@
+use serde_json::{json, Value};
+
+use crate::models::{migrate, NodeData, NodeError, Storage};
+
+/// Storage key of the document index.
+pub const INDEX_KEY: &str = "documents";
+
+/// Storage key of the document that existed before there were several. It
+/// stays the first document, and the one that is synced with the server.
+pub const PRIMARY_KEY: &str = "root";
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct DocumentInfo {
+    /// The storage key the document's tree is saved under.
+    pub key: String,
+    pub name: String,
+}
+
+/// The documents there are, in the order they are listed, and the one that
+/// is open.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DocumentIndex {
+    pub documents: Vec<DocumentInfo>,
+    pub active: String,
+}
+
+impl Default for DocumentIndex {
+    fn default() -> Self {
+        Self {
+            documents: vec![DocumentInfo {
+                key: PRIMARY_KEY.to_string(),
+                name: "Notes".to_string(),
+            }],
+            active: PRIMARY_KEY.to_string(),
+        }
+    }
+}
+
+fn new_key() -> String {
+    format!("document-{}", NodeData::next_id())
+}
+
+impl DocumentIndex {
+    pub fn get(&self, key: &str) -> Option<&DocumentInfo> {
+        self.documents.iter().find(|document| document.key == key)
+    }
+
+    fn position(&self, key: &str) -> Option<usize> {
+        self.documents
+            .iter()
+            .position(|document| document.key == key)
+    }
+
+    /// Makes the document with `key` the open one.
+    pub fn select(&mut self, key: &str) -> bool {
+        if self.get(key).is_none() {
+            return false;
+        }
+        self.active = key.to_string();
+        true
+    }
+
+    /// Adds an empty document at the end, opens it and returns its key.
+    pub fn create(&mut self, name: &str) -> String {
+        let key = new_key();
+        self.documents.push(DocumentInfo {
+            key: key.clone(),
+            name: name.to_string(),
+        });
+        self.active = key.clone();
+        key
+    }
+
+    pub fn rename(&mut self, key: &str, name: &str) -> bool {
+        match self
+            .documents
+            .iter_mut()
+            .find(|document| document.key == key)
+        {
+            Some(document) => {
+                document.name = name.to_string();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Adds a copy of the document with `key` after it, opens it and returns
+    /// its key. Copying the tree itself is up to the caller.
+    pub fn duplicate(&mut self, key: &str) -> Option<String> {
+        let index = self.position(key)?;
+        let copy = DocumentInfo {
+            key: new_key(),
+            name: format!("{} (copy)", self.documents[index].name),
+        };
+        let copy_key = copy.key.clone();
+        self.documents.insert(index + 1, copy);
+        self.active = copy_key.clone();
+        Some(copy_key)
+    }
+
+    /// Whether the document with `key` can be deleted: neither the last one,
+    /// nor the primary one, which would stop syncing.
+    pub fn can_delete(&self, key: &str) -> bool {
+        key != PRIMARY_KEY && self.documents.len() > 1 && self.get(key).is_some()
+    }
+
+    /// Removes the document with `key` from the index, opening its neighbour
+    /// if it was open. See `can_delete` for the documents that stay.
+    pub fn delete(&mut self, key: &str) -> bool {
+        if !self.can_delete(key) {
+            return false;
+        }
+        let Some(index) = self.position(key) else {
+            return false;
+        };
+        self.documents.remove(index);
+        if self.active == key {
+            let neighbour = index.min(self.documents.len() - 1);
+            self.active = self.documents[neighbour].key.clone();
+        }
+        true
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "active": self.active,
+            "documents": self
+                .documents
+                .iter()
+                .map(|document| json!({ "key": document.key, "name": document.name }))
+                .collect::<Vec<_>>()
+        })
+    }
+
+    pub fn from_json(value: &Value) -> Option<Self> {
+        let documents = value["documents"]
+            .as_array()?
+            .iter()
+            .map(|document| {
+                Some(DocumentInfo {
+                    key: document["key"].as_str()?.to_string(),
+                    name: document["name"].as_str()?.to_string(),
+                })
+            })
+            .collect::<Option<Vec<_>>>()?;
+        let first = documents.first()?.key.clone();
+        let mut index = Self {
+            documents,
+            active: first,
+        };
+        if let Some(active) = value["active"].as_str() {
+            index.select(active);
+        }
+        Some(index)
+    }
+
+    /// Loads the index from `storage`, or the default index with just the
+    /// primary document if there is none yet.
+    pub async fn load(storage: &dyn Storage) -> Result<Self, NodeError> {
+        let Some(json_string) = storage.load(INDEX_KEY).await? else {
+            return Ok(Self::default());
+        };
+        let json_value: Value = serde_json::from_str(&json_string)?;
+        Self::from_json(&json_value)
+            .ok_or_else(|| NodeError::schema("$", "expected a document index"))
+    }
+
+    pub async fn save(&self, storage: &dyn Storage) -> Result<(), NodeError> {
+        storage.save(INDEX_KEY, self.to_json().to_string()).await
+    }
+}
+
+/// Moves the index and the documents in it from `from` to `to`, unless `to`
+/// has an index already. Returns whether anything was moved.
+///
+/// Used when a user without documents on this device logs in, so that the
+/// ones made without a session become theirs.
+pub async fn claim(from: &dyn Storage, to: &dyn Storage) -> Result<bool, NodeError> {
+    if to.load(INDEX_KEY).await?.is_some() {
+        return Ok(false);
+    }
+    let stored = from.load(INDEX_KEY).await?.is_some();
+    let index = DocumentIndex::load(from).await?;
+    let mut moved = false;
+    for document in &index.documents {
+        moved |= migrate(from, to, &document.key).await?;
+    }
+    if stored || moved {
+        index.save(to).await?;
+        from.remove(INDEX_KEY).await?;
+    }
+    Ok(stored || moved)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::MemoryStore;
+    use futures::executor::block_on;
+
+    fn names(index: &DocumentIndex) -> Vec<&str> {
+        index
+            .documents
+            .iter()
+            .map(|document| document.name.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn test_create_rename_duplicate_delete() {
+        let mut index = DocumentIndex::default();
+        assert_eq!(names(&index), ["Notes"]);
+        assert_eq!(index.active, PRIMARY_KEY);
+
+        let work = index.create("Work");
+        assert_eq!(index.active, work);
+        assert!(index.rename(&work, "Project"));
+        assert!(!index.rename("missing", "Nothing"));
+
+        assert!(index.select(PRIMARY_KEY));
+        let copy = index.duplicate(PRIMARY_KEY).unwrap();
+        assert_eq!(names(&index), ["Notes", "Notes (copy)", "Project"]);
+        assert_eq!(index.active, copy);
+
+        // The primary document is synced, so it stays
+        assert!(!index.can_delete(PRIMARY_KEY));
+        assert!(!index.delete(PRIMARY_KEY));
+        assert!(index.can_delete(&copy));
+
+        // Deleting the open document opens the next one
+        assert!(index.delete(&copy));
+        assert_eq!(names(&index), ["Notes", "Project"]);
+        assert_eq!(index.active, work);
+
+        assert!(index.delete(&work));
+        assert_eq!(index.active, PRIMARY_KEY);
+        assert!(!index.delete(PRIMARY_KEY));
+        assert_eq!(names(&index), ["Notes"]);
+    }
+
+    #[test]
+    fn test_json_roundtrip() {
+        let mut index = DocumentIndex::default();
+        index.create("Work");
+        assert_eq!(DocumentIndex::from_json(&index.to_json()), Some(index));
+
+        // An unknown active document falls back to the first one
+        let json = json!({ "active": "gone", "documents": [{ "key": "root", "name": "Notes" }] });
+        assert_eq!(DocumentIndex::from_json(&json).unwrap().active, "root");
+        assert_eq!(DocumentIndex::from_json(&json!({ "documents": [] })), None);
+    }
+
+    #[test]
+    fn test_load_and_save() {
+        let storage = MemoryStore::default();
+        block_on(async {
+            assert_eq!(
+                DocumentIndex::load(&storage).await.unwrap(),
+                DocumentIndex::default()
+            );
+
+            let mut index = DocumentIndex::default();
+            index.create("Work");
+            index.save(&storage).await.unwrap();
+            assert_eq!(DocumentIndex::load(&storage).await.unwrap(), index);
+        });
+    }
+
+    #[test]
+    fn test_claim() {
+        let from = MemoryStore::default();
+        let to = MemoryStore::default();
+        block_on(async {
+            assert!(!claim(&from, &to).await.unwrap());
+            assert_eq!(to.load(INDEX_KEY).await.unwrap(), None);
+
+            // A tree saved before there was an index is claimed with it
+            from.save(PRIMARY_KEY, "notes".to_string()).await.unwrap();
+            assert!(claim(&from, &to).await.unwrap());
+            assert_eq!(
+                to.load(PRIMARY_KEY).await.unwrap().as_deref(),
+                Some("notes")
+            );
+            assert_eq!(from.load(PRIMARY_KEY).await.unwrap(), None);
+            assert_eq!(
+                DocumentIndex::load(&to).await.unwrap(),
+                DocumentIndex::default()
+            );
+
+            // Once the target has documents, those of the source stay put
+            let mut index = DocumentIndex::default();
+            let work = index.create("Work");
+            index.save(&from).await.unwrap();
+            from.save(&work, "work".to_string()).await.unwrap();
+            assert!(!claim(&from, &to).await.unwrap());
+            assert_eq!(from.load(&work).await.unwrap().as_deref(), Some("work"));
+            assert_eq!(to.load(&work).await.unwrap(), None);
+        });
+    }
+}
@@ -0,0 +1,489 @@
+use leptos::prelude::*;
+use serde_json::Value;
+
+use crate::models::{now_millis, Event, History, Node, NodeData, NodeId};
+
+/// The in-memory event log for a document.
+///
+/// Components obtain it from context and `dispatch` their edits through it so
+/// that every change is both applied to the tree and recorded, and can be
+/// undone. The tree is plain data; `root` is its projection into signals,
+/// which the views render.
+#[derive(Clone, Copy)]
+pub struct EventLog {
+    pub root: Node,
+    tree: StoredValue<NodeData>,
+    pub events: RwSignal<Vec<Event>>,
+    pub history: RwSignal<History>,
+    /// Counts the changes to the tree, logged or not, for views that derive
+    /// something from the whole tree.
+    pub revision: RwSignal<u64>,
+}
+
+impl EventLog {
+    pub fn new(root: Node) -> Self {
+        Self {
+            root,
+            tree: StoredValue::new(untrack(|| root.to_data())),
+            events: RwSignal::new(Vec::new()),
+            history: RwSignal::new(History::default()),
+            revision: RwSignal::new(0),
+        }
+    }
+
+    /// Reads the current tree. Nothing is tracked; views read `root`.
+    pub fn with_tree<T>(&self, f: impl FnOnce(&NodeData) -> T) -> T {
+        self.tree.with_value(f)
+    }
+
+    /// Replaces the tree without recording anything, e.g. with one rebuilt
+    /// from the server's events. Nodes that exist in both keep their fold
+    /// state, since it is local to this view.
+    pub fn reset(&self, mut tree: NodeData) {
+        self.tree
+            .with_value(|current| tree.keep_fold_state(current));
+        self.root.reconcile(&tree);
+        self.tree.set_value(tree);
+        self.revision.update(|revision| *revision += 1);
+    }
+
+    /// Applies `event` to the root and appends it to the log if it applied,
+    /// recording how to undo it.
+    pub fn dispatch(&self, event: Event) -> bool {
+        let event = event.relative_to(self.root.id.get_untracked());
+        let backward = self.with_tree(|tree| tree.inverse(&event));
+        let applied = self.commit(event.clone());
+        if applied {
+            self.history
+                .update(|history| history.record(event, backward, now_millis()));
+        }
+        applied
+    }
+
+    /// Applies `event` and appends it to the log, bypassing the history.
+    /// The logged event records the text and siblings it was made against,
+    /// so that it merges with events other replicas made concurrently.
+    fn commit(&self, event: Event) -> bool {
+        let event = self.with_tree(|tree| tree.anchor(event));
+        let applied = self.apply(&event);
+        if applied {
+            self.events.update(|events| events.push(event));
+        }
+        applied
+    }
+
+    /// Applies `event` to the tree without logging it, for events that were
+    /// logged elsewhere, e.g. in another tab showing the same document. They
+    /// are not undone here, nor sent to the server a second time.
+    pub fn apply(&self, event: &Event) -> bool {
+        let applied = self
+            .tree
+            .try_update_value(|tree| tree.apply(event))
+            .unwrap_or_default();
+        if applied {
+            self.with_tree(|tree| self.root.apply(event, tree));
+            self.revision.update(|revision| *revision += 1);
+        }
+        applied
+    }
+
+    /// Runs `f` so that all events it dispatches are undone in one step.
+    pub fn transaction<T>(&self, f: impl FnOnce() -> T) -> T {
+        self.history.update(History::begin);
+        let result = f();
+        self.history.update(History::end);
+        result
+    }
+
+    /// Reverts the latest step. The reverting events are logged like any
+    /// other, so they reach the server too.
+    pub fn undo(&self) -> bool {
+        let Some(events) = self.history.try_update(History::take_undo).flatten() else {
+            return false;
+        };
+        for event in events {
+            self.commit(event);
+        }
+        true
+    }
+
+    /// Dispatches the latest undone step again.
+    pub fn redo(&self) -> bool {
+        let Some(events) = self.history.try_update(History::take_redo).flatten() else {
+            return false;
+        };
+        for event in events {
+            self.commit(event);
+        }
+        true
+    }
+
+    /// Shows the children of the node with `id` if they are hidden.
+    pub fn unfold(&self, id: NodeId) {
+        if self.with_tree(|tree| tree.find(id).is_some_and(|node| !node.is_open)) {
+            self.dispatch(Event::Folded { id, open: true });
+        }
+    }
+
+    /// Adds `nodes` and their subtrees at `index` among the children of the
+    /// node with `parent`, or after them if `index` is `None`, as a single
+    /// step, and unfolds it. Returns whether anything was added.
+    pub fn insert_subtrees(
+        &self,
+        parent: NodeId,
+        index: Option<usize>,
+        nodes: Vec<NodeData>,
+    ) -> bool {
+        let Some(len) =
+            self.with_tree(|tree| tree.find(parent).map(|parent| parent.children.len()))
+        else {
+            return false;
+        };
+        let first = index.map_or(len, |index| index.min(len));
+        let mut events = Vec::new();
+        for (offset, node) in nodes.iter().enumerate() {
+            node.push_subtree_events(Some(parent), first + offset, &mut events);
+        }
+        if events.is_empty() {
+            return false;
+        }
+        self.transaction(|| {
+            for event in events {
+                self.dispatch(event);
+            }
+            self.unfold(parent);
+        });
+        true
+    }
+
+    /// Moves the node with `id` to `target`, a `(parent, index)` pair as
+    /// returned by `NodeData::indent_target` and friends, and unfolds the new
+    /// parent so the node stays visible.
+    pub fn move_to(&self, id: NodeId, target: Option<(NodeId, usize)>) -> bool {
+        let Some((parent, index)) = target else {
+            return false;
+        };
+        self.transaction(|| {
+            let applied = self.dispatch(Event::Moved {
+                id,
+                parent: Some(parent),
+                index,
+                after: None,
+            });
+            if applied {
+                self.unfold(parent);
+            }
+            applied
+        })
+    }
+
+    /// Makes the node with `id` the last child of its previous sibling.
+    pub fn indent(&self, id: NodeId) -> bool {
+        self.move_to(id, self.with_tree(|tree| tree.indent_target(id)))
+    }
+
+    /// Makes the node with `id` the next sibling of its parent.
+    pub fn outdent(&self, id: NodeId) -> bool {
+        self.move_to(id, self.with_tree(|tree| tree.outdent_target(id)))
+    }
+
+    /// Swaps the node with `id` with its previous (`-1`) or next (`1`) sibling.
+    pub fn reorder(&self, id: NodeId, offset: isize) -> bool {
+        self.move_to(id, self.with_tree(|tree| tree.reorder_target(id, offset)))
+    }
+
+    pub fn to_json(self) -> Value {
+        Value::Array(self.events.get().iter().map(Event::to_json).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texts(node: Node) -> Vec<String> {
+        node.children
+            .get()
+            .iter()
+            .map(|child| child.get().text.get())
+            .collect()
+    }
+
+    /// A log for a root with children of the given texts, and their ids.
+    fn log_with<const N: usize>(texts: [&str; N]) -> (EventLog, [NodeId; N]) {
+        let children = texts.map(|text| NodeData::new(false, text, vec![]));
+        let ids = children.each_ref().map(|child| child.id);
+        let root = Node::from_data(&NodeData::new(true, "root", children.to_vec()));
+        (EventLog::new(root), ids)
+    }
+
+    /// The log's tree and its projection agree.
+    fn assert_projected(log: EventLog) {
+        assert_eq!(log.with_tree(NodeData::clone), log.root.to_data());
+    }
+
+    #[test]
+    fn test_log_indent_outdent_reorder() {
+        let (log, [a, b, c]) = log_with(["A", "B", "C"]);
+        let root = log.root;
+        let node = |id| root.find(id).unwrap();
+
+        assert!(log.indent(b));
+        assert!(node(a).is_open.get());
+        assert_eq!(texts(root), ["A", "C"]);
+        assert_eq!(texts(node(a)), ["B"]);
+
+        assert!(!log.indent(a));
+        assert!(!log.reorder(b, 1));
+
+        assert!(log.reorder(c, -1));
+        assert_eq!(texts(root), ["C", "A"]);
+
+        assert!(log.outdent(b));
+        assert_eq!(texts(root), ["C", "A", "B"]);
+        assert!(node(a).children.get().is_empty());
+        assert_projected(log);
+
+        // Three moves and unfolding A
+        assert_eq!(log.events.get().len(), 4);
+        let initial = [(a, "A"), (b, "B"), (c, "C")]
+            .map(|(id, text)| NodeData::with_id(id, false, text, vec![]));
+        let mut rebuilt = NodeData::with_id(root.id(), true, "root", initial.to_vec());
+        rebuilt.replay(log.events.get().iter());
+        assert_eq!(rebuilt, log.root.to_data());
+    }
+
+    #[test]
+    fn test_log_insert_subtrees() {
+        let existing = NodeData::new(false, "existing", vec![]);
+        let parent = NodeData::new(false, "parent", vec![existing]);
+        let (parent, root) = (parent.id, NodeData::new(true, "root", vec![parent]));
+        let root = Node::from_data(&root);
+        let log = EventLog::new(root);
+        let before = root.to_json();
+
+        let imported = NodeData::from_markdown("- [x] one\n  - two\n- three\n");
+        assert!(log.insert_subtrees(parent, None, imported));
+        assert!(root.find(parent).unwrap().is_open.get());
+        assert_eq!(
+            root.find(parent).unwrap().to_data().to_markdown(),
+            "- [ ] parent\n  - [ ] existing\n  - [x] one\n    - [ ] two\n  - [ ] three\n"
+        );
+        assert_projected(log);
+
+        // The import is undone as a whole
+        assert!(log.undo());
+        assert_eq!(root.to_json(), before);
+        assert!(!log.insert_subtrees(parent, None, vec![]));
+        assert!(!log.insert_subtrees(NodeData::next_id(), None, NodeData::from_markdown("- one")));
+
+        // Pasted lines go right after the node they are pasted into
+        let pasted = NodeData::from_indented_text("first\n  nested\nsecond");
+        assert!(log.insert_subtrees(root.id(), Some(0), pasted));
+        assert_eq!(texts(root), ["first", "second", "parent"]);
+        assert_projected(log);
+    }
+
+    #[test]
+    fn test_log_undo_redo() {
+        let (log, [child]) = log_with(["child"]);
+        let root = log.root;
+        let before = root.to_json();
+        let added = NodeData::next_id();
+
+        log.transaction(|| {
+            log.dispatch(Event::Added {
+                id: added,
+                parent: Some(child),
+                index: 0,
+                after: None,
+                text: "grandchild".to_string(),
+            });
+            log.unfold(child);
+        });
+        log.dispatch(Event::Removed { id: child });
+        assert!(root.children.get().is_empty());
+
+        // Undoing the removal brings back the whole subtree, unfolded
+        assert!(log.undo());
+        let restored = root.find(added).unwrap();
+        assert_eq!(restored.text.get(), "grandchild");
+        assert!(root.find(child).unwrap().is_open.get());
+
+        // Adding and unfolding was one step
+        assert!(log.undo());
+        assert_eq!(root.to_json(), before);
+        assert!(!log.undo());
+
+        assert!(log.redo());
+        assert!(log.redo());
+        assert!(root.children.get().is_empty());
+        assert!(!log.redo());
+        assert_projected(log);
+
+        // Undo and redo are recorded like any other edit
+        assert_eq!(log.events.get().last(), Some(&Event::Removed { id: child }));
+    }
+
+    #[test]
+    fn test_dispatch_records_root_children_without_parent() {
+        let (log, [child]) = log_with(["child"]);
+        let root = log.root.id();
+        let added = NodeData::next_id();
+
+        log.dispatch(Event::Added {
+            id: added,
+            parent: Some(root),
+            index: 1,
+            after: None,
+            text: String::new(),
+        });
+        log.dispatch(Event::Moved {
+            id: added,
+            parent: Some(child),
+            index: 0,
+            after: None,
+        });
+        log.dispatch(Event::Moved {
+            id: added,
+            parent: Some(root),
+            index: 0,
+            after: None,
+        });
+
+        assert_eq!(
+            log.events.get(),
+            [
+                Event::Added {
+                    id: added,
+                    parent: None,
+                    index: 1,
+                    after: Some(child),
+                    text: String::new(),
+                },
+                Event::Moved {
+                    id: added,
+                    parent: Some(child),
+                    index: 0,
+                    after: None,
+                },
+                Event::Moved {
+                    id: added,
+                    parent: None,
+                    index: 0,
+                    after: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_logged_edits_record_replaced_text() {
+        let (log, [child]) = log_with(["child"]);
+
+        log.dispatch(Event::Edited {
+            id: child,
+            text: "edited".to_string(),
+            from: None,
+        });
+        assert!(log.undo());
+
+        assert_eq!(
+            log.events.get(),
+            [
+                Event::Edited {
+                    id: child,
+                    text: "edited".to_string(),
+                    from: Some("child".to_string()),
+                },
+                Event::Edited {
+                    id: child,
+                    text: "child".to_string(),
+                    from: Some("edited".to_string()),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_apply_does_not_log() {
+        let (log, [child]) = log_with(["child"]);
+
+        assert!(log.apply(&Event::Edited {
+            id: child,
+            text: "edited in another tab".to_string(),
+            from: None,
+        }));
+        assert_eq!(texts(log.root), ["edited in another tab"]);
+        assert!(!log.apply(&Event::Removed {
+            id: NodeData::next_id()
+        }));
+        // Views derived from the tree see the change all the same
+        assert_eq!(log.revision.get(), 1);
+        assert!(log.events.get().is_empty());
+        assert!(!log.history.with(History::can_undo));
+        assert_projected(log);
+    }
+
+    #[test]
+    fn test_reset_keeps_signals_and_fold_state() {
+        let (log, [a, b]) = log_with(["A", "B"]);
+        let a_signal = log.root.children.get()[0];
+
+        let mut remote = log.with_tree(NodeData::clone);
+        remote.find_mut(a).unwrap().is_open = true;
+        remote.find_mut(a).unwrap().text = "A, edited remotely".to_string();
+        remote.remove_child(b);
+        let revision = log.revision.get();
+        log.reset(remote);
+        assert_eq!(log.revision.get(), revision + 1);
+
+        assert_eq!(texts(log.root), ["A, edited remotely"]);
+        assert!(log.root.children.get()[0] == a_signal);
+        assert!(!log.root.find(a).unwrap().is_open.get());
+        assert_projected(log);
+        assert!(log.events.get().is_empty());
+    }
+
+    #[test]
+    fn test_replay_rebuilds_tree() {
+        let (log, []) = log_with([]);
+
+        let lunch = NodeData::next_id();
+        let pasta = NodeData::next_id();
+        let pesto = NodeData::next_id();
+        log.dispatch(Event::Added {
+            id: lunch,
+            parent: None,
+            index: 0,
+            after: None,
+            text: "make lunch".to_string(),
+        });
+        log.dispatch(Event::Edited {
+            id: lunch,
+            text: "make pasta for lunch".to_string(),
+            from: None,
+        });
+        log.dispatch(Event::Added {
+            id: pasta,
+            parent: Some(lunch),
+            index: 0,
+            after: None,
+            text: "cook pasta".to_string(),
+        });
+        log.dispatch(Event::Added {
+            id: pesto,
+            parent: Some(lunch),
+            index: 0,
+            after: None,
+            text: "add pesto".to_string(),
+        });
+        log.dispatch(Event::Removed { id: pasta });
+        assert_eq!(log.events.get().len(), 5);
+        assert_projected(log);
+
+        let mut rebuilt = NodeData::with_id(log.root.id(), true, "root", vec![]);
+        rebuilt.replay(log.events.get().iter());
+        assert_eq!(rebuilt.to_json(), log.root.to_json());
+    }
+}
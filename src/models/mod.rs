@@ -1,3 +1,10 @@
+mod documents;
+mod event;
 mod node;
+mod storage;
 
+pub use documents::*;
+pub use event::*;
 pub use node::*;
+pub use notes_core::*;
+pub use storage::*;
@@ -1,318 +1,296 @@
+use std::collections::HashMap;
+
 use leptos::prelude::*;
-use serde_json::{json, Value};
+use serde_json::Value;
+
+use crate::models::{Event, NodeData, NodeId};
 
+/// A `NodeData` tree held in signals, so that views update when it changes.
+///
+/// It is a projection: edits are applied to the plain tree of the
+/// `EventLog`, which then brings this one up to date with `apply` or
+/// `reconcile`.
 #[derive(Clone, Copy)]
 pub struct Node {
-    pub id: RwSignal<usize>,
+    pub id: RwSignal<NodeId>,
     pub is_open: RwSignal<bool>,
     pub text: RwSignal<String>,
+    pub done: RwSignal<bool>,
+    /// When the node was marked as done, in milliseconds since the Unix epoch.
+    /// `None` if it is not done or the time is unknown.
+    pub done_at: RwSignal<Option<u64>>,
     pub children: RwSignal<Vec<RwSignal<Node>>>,
 }
 
-impl Node {
-    pub fn new(is_open: bool, text: &str, children: Vec<Node>) -> Self {
-        static NEXT_ID: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(1);
-        let id = NEXT_ID.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
-
-        let child_signals: Vec<RwSignal<Node>> = children
-            .into_iter()
-            .map(|child_node| RwSignal::new(child_node))
-            .collect();
+/// Current time in milliseconds since the Unix epoch.
+pub fn now_millis() -> u64 {
+    #[cfg(target_arch = "wasm32")]
+    {
+        js_sys::Date::now() as u64
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_millis() as u64)
+            .unwrap_or_default()
+    }
+}
 
+impl Node {
+    /// Signals holding `data` and its subtree.
+    pub fn from_data(data: &NodeData) -> Self {
         Self {
-            id: RwSignal::new(id),
-            is_open: RwSignal::new(is_open),
-            text: RwSignal::new(text.to_string()),
-            children: RwSignal::new(child_signals),
+            id: RwSignal::new(data.id),
+            is_open: RwSignal::new(data.is_open),
+            text: RwSignal::new(data.text.clone()),
+            done: RwSignal::new(data.done),
+            done_at: RwSignal::new(data.done_at),
+            children: RwSignal::new(
+                data.children
+                    .iter()
+                    .map(|child| RwSignal::new(Node::from_data(child)))
+                    .collect(),
+            ),
         }
     }
 
-    pub fn from_json(value: &Value) -> Option<Self> {
-        let id = value["id"].as_u64()?.try_into().ok()?;
-        let is_open = value["is_open"].as_bool()?;
-        let text = value["text"].as_str()?.to_string();
-
-        let children_json = value["children"].as_array()?;
-        let children: Vec<Node> = children_json
-            .iter()
-            .filter_map(|child_json| Node::from_json(child_json))
-            .collect();
-
-        Some(Self {
-            id: RwSignal::new(id),
-            is_open: RwSignal::new(is_open),
-            text: RwSignal::new(text),
-            children: RwSignal::new(children.into_iter().map(RwSignal::new).collect()),
-        })
-    }
-
-    pub fn id(&self) -> usize {
-        self.id.get()
+    /// The tree as plain data. Inside an effect this subscribes to every
+    /// signal in it.
+    pub fn to_data(self) -> NodeData {
+        NodeData {
+            id: self.id.get(),
+            is_open: self.is_open.get(),
+            text: self.text.get(),
+            done: self.done.get(),
+            done_at: self.done_at.get(),
+            children: self
+                .children
+                .get()
+                .iter()
+                .map(|child| child.get().to_data())
+                .collect(),
+        }
     }
 
-    pub fn prepend_child(&self, child: Node) {
-        let child_signal = RwSignal::new(child);
-        self.children.update(|children| {
-            children.insert(0, child_signal);
-        });
+    pub fn to_json(self) -> Value {
+        self.to_data().to_json()
     }
 
-    pub fn remove_child(&self, id: usize) -> bool {
-        let mut success = false;
-        self.children.update(|children| {
-            if let Some(index) = children.iter().position(|child| child.get().id() == id) {
-                children.remove(index);
-                success = true;
-            }
-        });
-        success
+    pub fn id(&self) -> NodeId {
+        self.id.get()
     }
 
-    pub fn to_json(&self) -> Value {
-        let children: Vec<Value> = self
-            .children
-            .get()
+    /// Finds the node with `id` in the subtree rooted at `self`, including `self`.
+    pub fn find(&self, id: NodeId) -> Option<Node> {
+        if self.id.get_untracked() == id {
+            return Some(*self);
+        }
+        self.children
+            .get_untracked()
             .iter()
-            .map(|child_signal| child_signal.get().to_json())
-            .collect();
+            .find_map(|child| child.get_untracked().find(id))
+    }
 
-        json!({
-            "id": self.id.get(),
-            "is_open": self.is_open.get(),
-            "text": self.text.get(),
-            "children": children
+    /// The nodes from `self` down to the parent of the node with `id`, or
+    /// `None` if it is not in the subtree rooted at `self`.
+    pub fn ancestors(&self, id: NodeId) -> Option<Vec<Node>> {
+        if self.id.get_untracked() == id {
+            return Some(Vec::new());
+        }
+        self.children.get_untracked().iter().find_map(|child| {
+            let mut ancestors = child.get_untracked().ancestors(id)?;
+            ancestors.insert(0, *self);
+            Some(ancestors)
         })
     }
 
-    pub fn save_to_local_storage(&self, key: &str) -> Result<(), String> {
-        let json_value = self.to_json();
-        let json_string = json_value.to_string();
-
-        let window = window();
-
-        let storage = window
-            .local_storage()
-            .map_err(|_| "Failed to access localStorage".to_string())?
-            .ok_or_else(|| "localStorage not available".to_string())?;
-
-        storage
-            .set_item(key, &json_string)
-            .map_err(|err| format!("Failed to set localStorage item: {:?}", err))?;
-
-        Ok(())
+    /// Brings the projection up to date after `event` was applied to `tree`,
+    /// the data it projects. Changes to a single node only touch its signals;
+    /// changes to the structure reconcile the whole tree.
+    pub fn apply(&self, event: &Event, tree: &NodeData) {
+        match event {
+            Event::Edited { id, .. }
+            | Event::MarkedAsDone { id, .. }
+            | Event::MarkedAsUndone { id }
+            | Event::Folded { id, .. } => {
+                if let (Some(node), Some(data)) = (self.find(*id), tree.find(*id)) {
+                    node.update_fields(data);
+                }
+            }
+            Event::Added { .. } | Event::Removed { .. } | Event::Moved { .. } => {
+                self.reconcile(tree);
+            }
+        }
     }
 
-    pub fn load_from_local_storage(key: &str) -> Result<Self, String> {
-        let window = window();
-
-        let storage = window
-            .local_storage()
-            .map_err(|_| "Failed to access localStorage".to_string())?
-            .ok_or_else(|| "localStorage not available".to_string())?;
-
-        let json_string = storage
-            .get_item(key)
-            .map_err(|_| "Failed to get item from localStorage".to_string())?
-            .ok_or_else(|| format!("No item found with key: {}", key))?;
-
-        let json_value: Value = serde_json::from_str(&json_string)
-            .map_err(|err| format!("Failed to parse JSON: {}", err))?;
-
-        Self::from_json(&json_value).ok_or_else(|| "Failed to convert JSON to Node".to_string())
+    /// Sets the signals of this node, but not its children, that differ
+    /// from `data`.
+    fn update_fields(&self, data: &NodeData) {
+        if self.is_open.get_untracked() != data.is_open {
+            self.is_open.set(data.is_open);
+        }
+        if self.text.with_untracked(|text| *text != data.text) {
+            self.text.set(data.text.clone());
+        }
+        if self.done.get_untracked() != data.done {
+            self.done.set(data.done);
+        }
+        if self.done_at.get_untracked() != data.done_at {
+            self.done_at.set(data.done_at);
+        }
     }
 
-    pub fn remove_from_local_storage(key: &str) -> Result<(), String> {
-        let window = window();
+    /// Makes this tree look like `data` while keeping the signals of nodes
+    /// that exist in both, wherever they are, so views rendering them stay
+    /// in place.
+    pub fn reconcile(&self, data: &NodeData) {
+        fn collect(node: RwSignal<Node>, existing: &mut HashMap<NodeId, RwSignal<Node>>) {
+            let node_value = node.get_untracked();
+            existing.insert(node_value.id.get_untracked(), node);
+            for child in node_value.children.get_untracked() {
+                collect(child, existing);
+            }
+        }
 
-        let storage = window
-            .local_storage()
-            .map_err(|_| "Failed to access localStorage".to_string())?
-            .ok_or_else(|| "localStorage not available".to_string())?;
+        let mut existing = HashMap::new();
+        for child in self.children.get_untracked() {
+            collect(child, &mut existing);
+        }
+        self.reconcile_with(data, &existing);
+    }
 
-        storage
-            .remove_item(key)
-            .map_err(|err| format!("Failed to remove localStorage item: {:?}", err))?;
+    fn reconcile_with(&self, data: &NodeData, existing: &HashMap<NodeId, RwSignal<Node>>) {
+        self.update_fields(data);
+        let children: Vec<RwSignal<Node>> = data
+            .children
+            .iter()
+            .map(|child| match existing.get(&child.id) {
+                Some(signal) => {
+                    signal.get_untracked().reconcile_with(child, existing);
+                    *signal
+                }
+                None => RwSignal::new(Node::from_data(child)),
+            })
+            .collect();
 
-        Ok(())
+        if self.children.with_untracked(|current| *current != children) {
+            self.children.set(children);
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    #[cfg(target_arch = "wasm32")]
+    use crate::models::{LocalStore, Storage};
     use wasm_bindgen_test::*;
 
     wasm_bindgen_test_configure!(run_in_browser);
 
-    #[test]
-    fn test_prepend_child() {
-        let child1 = Node::new(false, "Child 1", vec![]);
-        let child2 = Node::new(false, "Child 2", vec![]);
-        let node = Node::new(true, "Parent", vec![child1]);
-
-        // Check initial state
-        let children = node.children.get();
-        assert_eq!(children.len(), 1);
-        assert_eq!(children[0].get().text.get(), "Child 1");
-
-        // Prepend child
-        node.prepend_child(child2);
-
-        // Check updated state
-        let updated_children = node.children.get();
-        assert_eq!(updated_children.len(), 2);
-        assert_eq!(updated_children[0].get().text.get(), "Child 2");
-        assert_eq!(updated_children[1].get().text.get(), "Child 1");
-    }
-
-    #[test]
-    fn test_remove_child() {
-        // Create parent with two children
-        let child1 = Node::new(false, "Child 1", vec![]);
-        let child2 = Node::new(false, "Child 2", vec![]);
-        let node = Node::new(true, "Parent", vec![child1, child2]);
-
-        // Get child IDs
-        let children = node.children.get();
-        let child1_id = children[0].get().id();
-        let child2_id = children[1].get().id();
-
-        // Remove first child
-        let result = node.remove_child(child1_id);
-        assert!(result);
-
-        // Check child was removed
-        let updated_children = node.children.get();
-        assert_eq!(updated_children.len(), 1);
-        assert_eq!(updated_children[0].get().id(), child2_id);
-
-        // Try to remove non-existent child
-        let result = node.remove_child(9999);
-        assert!(!result);
-
-        // Check children count remains unchanged
-        assert_eq!(node.children.get().len(), 1);
+    fn texts(node: Node) -> Vec<String> {
+        node.children
+            .get()
+            .iter()
+            .map(|child| child.get().text.get())
+            .collect()
     }
 
     #[test]
-    fn test_to_json() {
-        // Create a nested node structure
-        let grandchild = Node::new(false, "Grandchild", vec![]);
-        let child = Node::new(true, "Child", vec![grandchild]);
-        let parent = Node::new(true, "Parent", vec![child]);
-
-        // Get JSON representation
-        let json = parent.to_json();
-
-        // Verify top-level properties
-        assert_eq!(json["text"], "Parent");
-        assert_eq!(json["is_open"], true);
-        assert!(json["id"].is_number());
-
-        // Verify child structure
-        let children = &json["children"];
-        assert!(children.is_array());
-        assert_eq!(children.as_array().unwrap().len(), 1);
-
-        // Verify first child properties
-        let first_child = &children[0];
-        assert_eq!(first_child["text"], "Child");
-        assert_eq!(first_child["is_open"], true);
-
-        // Verify grandchild structure
-        let grandchildren = &first_child["children"];
-        assert!(grandchildren.is_array());
-        assert_eq!(grandchildren.as_array().unwrap().len(), 1);
-
-        // Verify grandchild properties
-        let first_grandchild = &grandchildren[0];
-        assert_eq!(first_grandchild["text"], "Grandchild");
-        assert_eq!(first_grandchild["is_open"], false);
-        assert!(first_grandchild["children"].is_array());
-        assert!(first_grandchild["children"].as_array().unwrap().is_empty());
+    fn test_data_roundtrip() {
+        let mut grandchild = NodeData::new(false, "Grandchild", vec![]);
+        grandchild.set_done(true, Some(1));
+        let child = NodeData::new(true, "Child", vec![grandchild]);
+        let data = NodeData::new(true, "Parent", vec![child]);
+
+        let node = Node::from_data(&data);
+        assert_eq!(texts(node), ["Child"]);
+        assert_eq!(node.to_data(), data);
+        assert_eq!(node.to_json(), data.to_json());
     }
 
     #[test]
-    fn test_from_json() {
-        // Create a JSON representation
-        let json = json!({
-            "id": 123,
-            "is_open": true,
-            "text": "Parent",
-            "children": [
-                {
-                    "id": 456,
-                    "is_open": false,
-                    "text": "Child",
-                    "children": []
-                }
-            ]
-        });
-
-        // Parse JSON to Node
-        let node = Node::from_json(&json).unwrap();
-
-        // Verify top-level properties
-        assert_eq!(node.id.get(), 123);
-        assert_eq!(node.is_open.get(), true);
-        assert_eq!(node.text.get(), "Parent");
-
-        // Verify children
-        let children = node.children.get();
-        assert_eq!(children.len(), 1);
-
-        // Verify child properties
-        let child = children[0].get();
-        assert_eq!(child.id.get(), 456);
-        assert_eq!(child.is_open.get(), false);
-        assert_eq!(child.text.get(), "Child");
-        assert_eq!(child.children.get().len(), 0);
+    fn test_reconcile() {
+        let a1 = NodeData::new(false, "A1", vec![]);
+        let a = NodeData::new(true, "A", vec![a1]);
+        let b = NodeData::new(false, "B", vec![]);
+        let (a_id, a1_id, b_id) = (a.id, a.children[0].id, b.id);
+        let mut data = NodeData::new(true, "Root", vec![a, b]);
+        let root = Node::from_data(&data);
+        let a = root.find(a_id).unwrap();
+        let a1 = root.find(a1_id).unwrap();
+
+        let a_edited = data.find_mut(a_id).unwrap();
+        a_edited.text = "A edited".to_string();
+        a_edited.is_open = false;
+        data.find_mut(b_id).unwrap().set_done(true, Some(5));
+        data.move_child(a_id, data.id, 1);
+        data.move_child(a1_id, b_id, 0);
+        let c = NodeData::new(false, "C", vec![]);
+        let c_id = c.id;
+        data.find_mut(a_id).unwrap().insert_child_at(0, c);
+
+        let a_signal = root.children.get()[0];
+        root.reconcile(&data);
+        assert_eq!(root.to_data(), data);
+
+        assert_eq!(texts(root), ["B", "A edited"]);
+        assert!(!a.is_open.get());
+        assert_eq!(a.children.get()[0].get().id(), c_id);
+        // Existing nodes keep their signals, also when they moved elsewhere
+        assert!(root.children.get()[1] == a_signal);
+        assert_eq!(root.find(a1_id).unwrap().text, a1.text);
     }
 
     #[test]
-    fn test_json_roundtrip() {
-        // Create a nested node structure
-        let grandchild = Node::new(false, "Grandchild", vec![]);
-        let child = Node::new(true, "Child", vec![grandchild]);
-        let original = Node::new(true, "Parent", vec![child]);
-
-        // Convert to JSON and back
-        let json = original.to_json();
-        let roundtrip = Node::from_json(&json).unwrap();
-
-        // Verify properties survived the roundtrip
-        assert_eq!(roundtrip.id.get(), original.id.get());
-        assert_eq!(roundtrip.is_open.get(), original.is_open.get());
-        assert_eq!(roundtrip.text.get(), original.text.get());
-
-        // Verify children
-        let original_children = original.children.get();
-        let roundtrip_children = roundtrip.children.get();
-        assert_eq!(roundtrip_children.len(), original_children.len());
-
-        // Verify first child
-        let original_child = original_children[0].get();
-        let roundtrip_child = roundtrip_children[0].get();
-        assert_eq!(roundtrip_child.id.get(), original_child.id.get());
-        assert_eq!(roundtrip_child.is_open.get(), original_child.is_open.get());
-        assert_eq!(roundtrip_child.text.get(), original_child.text.get());
+    fn test_apply_updates_projection() {
+        let child = NodeData::new(false, "Child", vec![]);
+        let id = child.id;
+        let mut data = NodeData::new(true, "Root", vec![child]);
+        let root = Node::from_data(&data);
+        let node = root.find(id).unwrap();
+
+        for event in [
+            Event::Edited {
+                id,
+                text: "Edited".to_string(),
+                from: None,
+            },
+            Event::MarkedAsDone { id, at: Some(3) },
+            Event::Added {
+                id: NodeData::next_id(),
+                parent: Some(id),
+                index: 0,
+                after: None,
+                text: "Grandchild".to_string(),
+            },
+            Event::Folded { id, open: true },
+        ] {
+            assert!(data.apply(&event));
+            root.apply(&event, &data);
+            assert_eq!(root.to_data(), data);
+        }
+        assert_eq!(texts(node), ["Grandchild"]);
+        assert!(node.is_open.get());
     }
 
+    #[cfg(target_arch = "wasm32")]
     #[wasm_bindgen_test]
-    fn test_local_storage_save_and_load() {
+    async fn test_local_storage_save_and_load() {
         // Create a unique key for this test to avoid conflicts
         let test_key = format!("test_node_{}", js_sys::Date::now());
 
         // Create a node to save
-        let child = Node::new(false, "Test Child", vec![]);
-        let original = Node::new(true, "Test Parent", vec![child]);
+        let child = NodeData::new(false, "Test Child", vec![]);
+        let original = Node::from_data(&NodeData::new(true, "Test Parent", vec![child]));
 
         // Save to localStorage
-        let save_result = original.save_to_local_storage(&test_key);
+        let save_result = original.save(&LocalStore, &test_key).await;
         assert!(save_result.is_ok(), "Saving to localStorage should succeed");
 
         // Load from localStorage
-        let loaded_result = Node::load_from_local_storage(&test_key);
+        let loaded_result = Node::load(&LocalStore, &test_key).await;
         assert!(
             loaded_result.is_ok(),
             "Loading from localStorage should succeed"
@@ -338,14 +316,14 @@ mod tests {
         assert_eq!(loaded_child.text.get(), original_child.text.get());
 
         // Clean up
-        let remove_result = Node::remove_from_local_storage(&test_key);
+        let remove_result = LocalStore.remove(&test_key).await;
         assert!(
             remove_result.is_ok(),
             "Removing from localStorage should succeed"
         );
 
         // Verify removal
-        let load_after_remove = Node::load_from_local_storage(&test_key);
+        let load_after_remove = Node::load(&LocalStore, &test_key).await;
         assert!(
             load_after_remove.is_err(),
             "Loading after removal should fail"
@@ -0,0 +1,505 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::rc::Rc;
+
+use js_sys::Promise;
+use leptos::logging::warn;
+use leptos::prelude::window;
+use leptos::web_sys::{
+    DomException, IdbDatabase, IdbObjectStore, IdbOpenDbRequest, IdbRequest, IdbTransaction,
+    IdbTransactionMode,
+};
+use serde_json::Value;
+use wasm_bindgen::closure::Closure;
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+
+use crate::models::{schema_version, Node, NodeData, NodeError, SCHEMA_VERSION};
+
+pub type StorageFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T, NodeError>> + 'a>>;
+
+/// Turns an exception thrown by a storage API into a `NodeError`, telling a
+/// full storage apart from other failures.
+fn storage_error(err: JsValue, context: &str) -> NodeError {
+    match err.dyn_ref::<DomException>() {
+        Some(exception) if exception.name() == "QuotaExceededError" => NodeError::QuotaExceeded,
+        Some(exception) => {
+            NodeError::StorageUnavailable(format!("{}: {}", context, exception.message()))
+        }
+        None => NodeError::StorageUnavailable(format!("{}: {:?}", context, err)),
+    }
+}
+
+/// A place to keep documents between sessions, as JSON strings by key.
+///
+/// The methods return boxed futures so that the backend can be chosen at
+/// runtime and kept as an `Rc<dyn Storage>`.
+pub trait Storage {
+    /// The value saved under `key`, or `None` if there is none.
+    fn load<'a>(&'a self, key: &'a str) -> StorageFuture<'a, Option<String>>;
+
+    fn save<'a>(&'a self, key: &'a str, value: String) -> StorageFuture<'a, ()>;
+
+    fn remove<'a>(&'a self, key: &'a str) -> StorageFuture<'a, ()>;
+}
+
+/// `window.localStorage`: synchronous and limited to a few megabytes, but
+/// available everywhere.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LocalStore;
+
+impl LocalStore {
+    pub fn is_supported() -> bool {
+        Self::storage().is_ok()
+    }
+
+    fn storage() -> Result<leptos::web_sys::Storage, NodeError> {
+        window()
+            .local_storage()
+            .map_err(|err| storage_error(err, "Failed to access localStorage"))?
+            .ok_or_else(|| NodeError::StorageUnavailable("localStorage not available".into()))
+    }
+}
+
+impl Storage for LocalStore {
+    fn load<'a>(&'a self, key: &'a str) -> StorageFuture<'a, Option<String>> {
+        Box::pin(async move {
+            Self::storage()?
+                .get_item(key)
+                .map_err(|err| storage_error(err, "Failed to get item from localStorage"))
+        })
+    }
+
+    fn save<'a>(&'a self, key: &'a str, value: String) -> StorageFuture<'a, ()> {
+        Box::pin(async move {
+            Self::storage()?
+                .set_item(key, &value)
+                .map_err(|err| storage_error(err, "Failed to set localStorage item"))
+        })
+    }
+
+    fn remove<'a>(&'a self, key: &'a str) -> StorageFuture<'a, ()> {
+        Box::pin(async move {
+            Self::storage()?
+                .remove_item(key)
+                .map_err(|err| storage_error(err, "Failed to remove localStorage item"))
+        })
+    }
+}
+
+/// Values in memory, for tests and for running without persistence.
+#[derive(Clone, Debug, Default)]
+pub struct MemoryStore {
+    values: Rc<RefCell<HashMap<String, String>>>,
+}
+
+impl Storage for MemoryStore {
+    fn load<'a>(&'a self, key: &'a str) -> StorageFuture<'a, Option<String>> {
+        Box::pin(async move { Ok(self.values.borrow().get(key).cloned()) })
+    }
+
+    fn save<'a>(&'a self, key: &'a str, value: String) -> StorageFuture<'a, ()> {
+        Box::pin(async move {
+            self.values.borrow_mut().insert(key.to_string(), value);
+            Ok(())
+        })
+    }
+
+    fn remove<'a>(&'a self, key: &'a str) -> StorageFuture<'a, ()> {
+        Box::pin(async move {
+            self.values.borrow_mut().remove(key);
+            Ok(())
+        })
+    }
+}
+
+/// The documents of one user in another storage, under keys prefixed with
+/// `user-<id>/`. Without a user the keys are those of the storage itself,
+/// where documents were kept before there were accounts.
+#[derive(Clone)]
+pub struct UserStore {
+    storage: Rc<dyn Storage>,
+    prefix: String,
+}
+
+impl UserStore {
+    pub fn new(storage: Rc<dyn Storage>, user_id: Option<i64>) -> Self {
+        let prefix = user_id
+            .map(|user_id| format!("user-{}/", user_id))
+            .unwrap_or_default();
+        Self { storage, prefix }
+    }
+
+    /// The key that `key` is saved under in the underlying storage.
+    pub fn key(&self, key: &str) -> String {
+        format!("{}{}", self.prefix, key)
+    }
+}
+
+impl Storage for UserStore {
+    fn load<'a>(&'a self, key: &'a str) -> StorageFuture<'a, Option<String>> {
+        Box::pin(async move { self.storage.load(&self.key(key)).await })
+    }
+
+    fn save<'a>(&'a self, key: &'a str, value: String) -> StorageFuture<'a, ()> {
+        Box::pin(async move { self.storage.save(&self.key(key), value).await })
+    }
+
+    fn remove<'a>(&'a self, key: &'a str) -> StorageFuture<'a, ()> {
+        Box::pin(async move { self.storage.remove(&self.key(key)).await })
+    }
+}
+
+const IDB_NAME: &str = "nyx";
+const IDB_VERSION: u32 = 1;
+const IDB_STORE: &str = "documents";
+
+/// IndexedDB: asynchronous and not limited to a few megabytes. Values are
+/// kept in the `documents` object store of the `nyx` database.
+///
+/// The database is opened on first use, and the connection kept until
+/// another tab needs to upgrade it.
+#[derive(Clone, Debug, Default)]
+pub struct IndexedDbStore {
+    db: Rc<RefCell<Option<IdbDatabase>>>,
+}
+
+fn js_error(err: JsValue) -> NodeError {
+    storage_error(err, "IndexedDB error")
+}
+
+/// Waits for the `success` or `error` event of `request`.
+async fn finished(request: &IdbRequest) -> Result<JsValue, NodeError> {
+    let promise = Promise::new(&mut |resolve, reject| {
+        request.set_onsuccess(Some(&resolve));
+        request.set_onerror(Some(&reject));
+    });
+    let result = JsFuture::from(promise).await;
+    request.set_onsuccess(None);
+    request.set_onerror(None);
+    if result.is_err() {
+        return Err(match request.error().ok().flatten() {
+            Some(err) => js_error(err.into()),
+            None => NodeError::StorageUnavailable("IndexedDB error".into()),
+        });
+    }
+    request.result().map_err(js_error)
+}
+
+/// Waits for `transaction` to commit. Writes can still fail after their
+/// requests succeeded, e.g. when the quota is exceeded, which aborts it.
+fn committed(transaction: IdbTransaction) -> impl Future<Output = Result<(), NodeError>> {
+    let promise = Promise::new(&mut |resolve, reject| {
+        transaction.set_oncomplete(Some(&resolve));
+        transaction.set_onabort(Some(&reject));
+        transaction.set_onerror(Some(&reject));
+    });
+    async move {
+        let result = JsFuture::from(promise).await;
+        transaction.set_oncomplete(None);
+        transaction.set_onabort(None);
+        transaction.set_onerror(None);
+        match (result, transaction.error()) {
+            (Ok(_), _) => Ok(()),
+            (Err(_), Some(err)) => Err(js_error(err.into())),
+            (Err(_), None) => Err(NodeError::StorageUnavailable(
+                "IndexedDB transaction aborted".into(),
+            )),
+        }
+    }
+}
+
+impl IndexedDbStore {
+    pub fn is_supported() -> bool {
+        matches!(window().indexed_db(), Ok(Some(_)))
+    }
+
+    async fn open() -> Result<IdbDatabase, NodeError> {
+        let factory = window()
+            .indexed_db()
+            .map_err(js_error)?
+            .ok_or_else(|| NodeError::StorageUnavailable("IndexedDB not available".into()))?;
+        let request: IdbOpenDbRequest = factory
+            .open_with_u32(IDB_NAME, IDB_VERSION)
+            .map_err(js_error)?;
+
+        let upgrade = Closure::once_into_js({
+            let request = request.clone();
+            move || {
+                if let Ok(db) = request.result().and_then(|db| db.dyn_into::<IdbDatabase>()) {
+                    if !db.object_store_names().contains(IDB_STORE) {
+                        let _ = db.create_object_store(IDB_STORE);
+                    }
+                }
+            }
+        });
+        request.set_onupgradeneeded(Some(upgrade.unchecked_ref()));
+        // Opening waits while a tab with an older version keeps it open
+        let blocked = Closure::once_into_js(|| {
+            warn!("IndexedDB is blocked until other tabs of the app are closed");
+        });
+        request.set_onblocked(Some(blocked.unchecked_ref()));
+
+        let db = finished(&request).await;
+        request.set_onupgradeneeded(None);
+        request.set_onblocked(None);
+        db?.dyn_into().map_err(js_error)
+    }
+
+    /// The open connection, opening it first if there is none.
+    async fn database(&self) -> Result<IdbDatabase, NodeError> {
+        if let Some(db) = self.db.borrow().as_ref() {
+            return Ok(db.clone());
+        }
+        let db = Self::open().await?;
+        // Another call may have opened it in the meantime
+        if let Some(open) = self.db.borrow().as_ref() {
+            db.close();
+            return Ok(open.clone());
+        }
+
+        // Let go of the connection when another tab upgrades the database,
+        // instead of blocking it. The next call opens the new version.
+        let versionchange = Closure::once_into_js({
+            let cached = self.db.clone();
+            let db = db.clone();
+            move || {
+                db.close();
+                cached.borrow_mut().take();
+            }
+        });
+        db.set_onversionchange(Some(versionchange.unchecked_ref()));
+        *self.db.borrow_mut() = Some(db.clone());
+        Ok(db)
+    }
+
+    async fn object_store(&self, mode: IdbTransactionMode) -> Result<IdbObjectStore, NodeError> {
+        let store = self
+            .database()
+            .await?
+            .transaction_with_str_and_mode(IDB_STORE, mode)
+            .and_then(|transaction| transaction.object_store(IDB_STORE));
+        if store.is_err() {
+            // The connection may have been closed by the browser
+            self.db.borrow_mut().take();
+        }
+        store.map_err(js_error)
+    }
+}
+
+impl Storage for IndexedDbStore {
+    fn load<'a>(&'a self, key: &'a str) -> StorageFuture<'a, Option<String>> {
+        Box::pin(async move {
+            let store = self.object_store(IdbTransactionMode::Readonly).await?;
+            let request = store.get(&JsValue::from_str(key)).map_err(js_error)?;
+            Ok(finished(&request).await?.as_string())
+        })
+    }
+
+    fn save<'a>(&'a self, key: &'a str, value: String) -> StorageFuture<'a, ()> {
+        Box::pin(async move {
+            let store = self.object_store(IdbTransactionMode::Readwrite).await?;
+            let committed = committed(store.transaction());
+            let request = store
+                .put_with_key(&JsValue::from_str(&value), &JsValue::from_str(key))
+                .map_err(js_error)?;
+            finished(&request).await?;
+            committed.await
+        })
+    }
+
+    fn remove<'a>(&'a self, key: &'a str) -> StorageFuture<'a, ()> {
+        Box::pin(async move {
+            let store = self.object_store(IdbTransactionMode::Readwrite).await?;
+            let committed = committed(store.transaction());
+            let request = store.delete(&JsValue::from_str(key)).map_err(js_error)?;
+            finished(&request).await?;
+            committed.await
+        })
+    }
+}
+
+/// Moves the value under `key` from `from` to `to`, unless `to` already has
+/// one. Returns whether anything was moved.
+///
+/// Used on startup to carry documents saved in localStorage by earlier
+/// versions over to IndexedDB.
+pub async fn migrate(from: &dyn Storage, to: &dyn Storage, key: &str) -> Result<bool, NodeError> {
+    if to.load(key).await?.is_some() {
+        return Ok(false);
+    }
+    let Some(value) = from.load(key).await? else {
+        return Ok(false);
+    };
+    to.save(key, value).await?;
+    from.remove(key).await?;
+    Ok(true)
+}
+
+/// Copies the value under `key` to `<key>_corrupt`, so that it survives
+/// `key` being overwritten after it could not be read. Returns the key of the
+/// copy, or `None` if nothing is saved under `key`.
+pub async fn keep_corrupt(storage: &dyn Storage, key: &str) -> Result<Option<String>, NodeError> {
+    let Some(value) = storage.load(key).await? else {
+        return Ok(None);
+    };
+    let copy = format!("{}_corrupt", key);
+    storage.save(&copy, value).await?;
+    Ok(Some(copy))
+}
+
+impl Node {
+    pub async fn save(self, storage: &dyn Storage, key: &str) -> Result<(), NodeError> {
+        storage
+            .save(key, self.to_data().to_document().to_string())
+            .await
+    }
+
+    /// Loads the tree saved under `key`. A tree saved by an earlier version
+    /// is saved again right away once it is upgraded, since the upgrade gives
+    /// nodes with numeric ids new ones every time it runs.
+    pub async fn load(storage: &dyn Storage, key: &str) -> Result<Self, NodeError> {
+        let json_string = storage
+            .load(key)
+            .await?
+            .ok_or_else(|| NodeError::KeyMissing(key.to_string()))?;
+
+        let json_value: Value = serde_json::from_str(&json_string)?;
+        let upgraded = schema_version(&json_value).is_ok_and(|version| version < SCHEMA_VERSION);
+        let node = Self::from_data(&NodeData::from_document(json_value)?);
+        if upgraded {
+            if let Err(err) = node.save(storage, key).await {
+                warn!("Failed to save {} after upgrading it: {}", key, err);
+            }
+        }
+        Ok(node)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::executor::block_on;
+
+    #[test]
+    fn test_memory_store_save_and_load() {
+        let storage = MemoryStore::default();
+        let child = NodeData::new(false, "Child", vec![]);
+        let original = Node::from_data(&NodeData::new(true, "Parent", vec![child]));
+
+        block_on(async {
+            original.save(&storage, "root").await.unwrap();
+            let loaded = Node::load(&storage, "root").await.unwrap();
+            assert_eq!(loaded.to_json(), original.to_json());
+
+            // Trees are saved in a versioned envelope, and older bare trees
+            // are upgraded on load
+            let saved = storage.load("root").await.unwrap().unwrap();
+            let saved: Value = serde_json::from_str(&saved).unwrap();
+            assert_eq!(saved["version"], SCHEMA_VERSION);
+            storage
+                .save("root", original.to_json().to_string())
+                .await
+                .unwrap();
+            let loaded = Node::load(&storage, "root").await.unwrap();
+            assert_eq!(loaded.to_json(), original.to_json());
+
+            storage.remove("root").await.unwrap();
+            assert_eq!(
+                Node::load(&storage, "root").await.err(),
+                Some(NodeError::KeyMissing("root".to_string()))
+            );
+
+            storage.save("root", "{".to_string()).await.unwrap();
+            assert!(matches!(
+                Node::load(&storage, "root").await,
+                Err(NodeError::JsonSyntax(_))
+            ));
+        });
+    }
+
+    #[test]
+    fn test_upgraded_tree_keeps_its_ids() {
+        let storage = MemoryStore::default();
+        let v0 = include_str!("../../core/src/fixtures/v0.json");
+
+        block_on(async {
+            storage.save("root", v0.to_string()).await.unwrap();
+            let first = Node::load(&storage, "root").await.unwrap();
+            // Saved in the current format with the new UUIDs, so that the
+            // next load does not make up others
+            let saved = storage.load("root").await.unwrap().unwrap();
+            let saved: Value = serde_json::from_str(&saved).unwrap();
+            assert_eq!(saved["version"], SCHEMA_VERSION);
+            let second = Node::load(&storage, "root").await.unwrap();
+            assert_eq!(second.to_json(), first.to_json());
+        });
+    }
+
+    #[test]
+    fn test_migrate() {
+        let from = MemoryStore::default();
+        let to = MemoryStore::default();
+
+        block_on(async {
+            assert!(!migrate(&from, &to, "root").await.unwrap());
+
+            from.save("root", "old".to_string()).await.unwrap();
+            assert!(migrate(&from, &to, "root").await.unwrap());
+            assert_eq!(to.load("root").await.unwrap().as_deref(), Some("old"));
+            assert_eq!(from.load("root").await.unwrap(), None);
+
+            // Existing data in the target is never overwritten
+            from.save("root", "older".to_string()).await.unwrap();
+            assert!(!migrate(&from, &to, "root").await.unwrap());
+            assert_eq!(to.load("root").await.unwrap().as_deref(), Some("old"));
+        });
+    }
+
+    #[test]
+    fn test_user_store() {
+        let storage = MemoryStore::default();
+        let anonymous = UserStore::new(Rc::new(storage.clone()), None);
+        let user = UserStore::new(Rc::new(storage.clone()), Some(7));
+
+        block_on(async {
+            anonymous.save("root", "mine".to_string()).await.unwrap();
+            user.save("root", "theirs".to_string()).await.unwrap();
+            assert_eq!(storage.load("root").await.unwrap().as_deref(), Some("mine"));
+            assert_eq!(
+                storage.load("user-7/root").await.unwrap().as_deref(),
+                Some("theirs")
+            );
+            assert_eq!(user.load("root").await.unwrap().as_deref(), Some("theirs"));
+
+            user.remove("root").await.unwrap();
+            assert_eq!(user.load("root").await.unwrap(), None);
+            assert_eq!(
+                anonymous.load("root").await.unwrap().as_deref(),
+                Some("mine")
+            );
+        });
+    }
+
+    #[test]
+    fn test_keep_corrupt() {
+        let storage = MemoryStore::default();
+
+        block_on(async {
+            assert_eq!(keep_corrupt(&storage, "root").await.unwrap(), None);
+
+            storage.save("root", "{".to_string()).await.unwrap();
+            assert_eq!(
+                keep_corrupt(&storage, "root").await.unwrap().as_deref(),
+                Some("root_corrupt")
+            );
+            assert_eq!(
+                storage.load("root_corrupt").await.unwrap().as_deref(),
+                Some("{")
+            );
+            assert_eq!(storage.load("root").await.unwrap().as_deref(), Some("{"));
+        });
+    }
+}
@@ -0,0 +1,90 @@
+use serde_json::json;
+
+use crate::sync::{fetch_json, load_item, remove_item, save_item, SyncConfig};
+
+/// localStorage key of the session of the logged in user.
+const SESSION_KEY: &str = "session";
+
+/// A session on the server, kept across reloads until the user logs out.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Session {
+    pub user_id: i64,
+    pub username: String,
+    pub token: String,
+}
+
+impl Session {
+    /// The session saved by the last login, if any.
+    pub fn load() -> Option<Self> {
+        let value = load_item(SESSION_KEY)?;
+        Some(Self {
+            user_id: value["user_id"].as_i64()?,
+            username: value["username"].as_str()?.to_string(),
+            token: value["token"].as_str()?.to_string(),
+        })
+    }
+
+    fn save(&self) {
+        save_item(
+            SESSION_KEY,
+            &json!({
+                "user_id": self.user_id,
+                "username": self.username,
+                "token": self.token,
+            }),
+        );
+    }
+
+    /// Logs in with an existing account, or creates it with `signup`, and
+    /// saves the new session. Fails with a message to show the user.
+    pub async fn log_in(username: &str, password: &str, signup: bool) -> Result<Self, String> {
+        let path = if signup { "signup" } else { "login" };
+        let body = json!({ "username": username, "password": password });
+        let response = fetch_json("POST", &url(path), None, Some(body))
+            .await
+            .map_err(|err| err.message())?;
+        let session = Self {
+            user_id: response["user_id"]
+                .as_i64()
+                .ok_or("invalid response from the server")?,
+            username: username.trim().to_string(),
+            token: response["token"]
+                .as_str()
+                .ok_or("invalid response from the server")?
+                .to_string(),
+        };
+        session.save();
+        Ok(session)
+    }
+
+    /// Forgets the session here and ends it on the server. Local events of
+    /// the user not synced yet stay until they log in again.
+    pub async fn log_out(self) {
+        Self::forget();
+        // The session expires by itself if the server cannot be reached
+        let _ = fetch_json("POST", &url("logout"), Some(&self.token), None).await;
+    }
+
+    /// Forgets the saved session without telling the server, e.g. because
+    /// the server no longer accepts it.
+    pub fn forget() {
+        remove_item(SESSION_KEY);
+    }
+
+    /// Where and as whom to sync.
+    pub fn sync_config(&self) -> SyncConfig {
+        SyncConfig {
+            user_id: self.user_id,
+            token: Some(self.token.clone()),
+            ..SyncConfig::default()
+        }
+    }
+}
+
+fn url(path: &str) -> String {
+    format!(
+        "{}/{}",
+        SyncConfig::default().server_url.trim_end_matches('/'),
+        path
+    )
+}
@@ -0,0 +1,846 @@
+use std::collections::HashSet;
+use std::time::Duration;
+
+use leptos::prelude::*;
+use leptos::task::spawn_local;
+use leptos::web_sys::{EventSource, MessageEvent, Request, RequestInit, Response, Storage};
+use serde_json::{json, Value};
+use uuid::Uuid;
+use wasm_bindgen::closure::Closure;
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+
+use crate::models::{Event, EventLog, NodeData, NodeId};
+
+/// localStorage key of the local events the server has not accepted yet, of
+/// all tabs. The keys are suffixed with the user id, so every user has their
+/// own.
+/// Earlier versions saved them without, see `SyncClient::adopt_legacy`.
+const OUTBOX_KEY: &str = "sync_outbox";
+/// localStorage key of the id of the last server event folded into the base.
+const CURSOR_KEY: &str = "sync_cursor";
+/// localStorage key of the tree as of the cursor, without local events.
+const BASE_KEY: &str = "sync_base";
+
+/// How often to look for remote events while the app is open. Most arrive
+/// sooner through the events stream; this catches up when it is unavailable.
+const POLL_INTERVAL: Duration = Duration::from_secs(10);
+/// How long to wait after a local edit before pushing it, so that typing a
+/// word is sent as one request.
+const PUSH_DELAY: Duration = Duration::from_millis(500);
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum SyncStatus {
+    Synced,
+    Syncing,
+    /// The server could not be reached; local events stay in the outbox.
+    Offline,
+    /// The server answered with an error or something unreadable.
+    Failed(String),
+    /// The server no longer accepts the session, e.g. because it expired or
+    /// was logged out elsewhere. Local events stay in the outbox until the
+    /// user logs in again.
+    LoggedOut,
+}
+
+pub(crate) enum SyncError {
+    Offline,
+    /// The server answered 401 Unauthorized.
+    LoggedOut,
+    Failed(String),
+}
+
+impl SyncError {
+    pub(crate) fn message(&self) -> String {
+        match self {
+            SyncError::Offline => "the server cannot be reached".to_string(),
+            SyncError::LoggedOut => "the session has expired".to_string(),
+            SyncError::Failed(message) => message.clone(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct SyncConfig {
+    pub server_url: String,
+    pub user_id: i64,
+    /// The session token sent with every request.
+    pub token: Option<String>,
+}
+
+impl Default for SyncConfig {
+    /// The server from `NYX_SERVER_URL` at build time, or a local one.
+    fn default() -> Self {
+        Self {
+            server_url: option_env!("NYX_SERVER_URL")
+                .unwrap_or("http://localhost:8000")
+                .to_string(),
+            user_id: 0,
+            token: None,
+        }
+    }
+}
+
+/// A local event waiting in the outbox. Its id is chosen when the event is
+/// made and sent with every push of it, so the server stores it only once
+/// however often it is pushed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Outgoing {
+    pub event_id: Uuid,
+    pub event: Event,
+}
+
+impl Outgoing {
+    pub fn new(event: Event) -> Self {
+        Self {
+            event_id: Uuid::new_v4(),
+            event,
+        }
+    }
+
+    /// The event as saved in the outbox.
+    pub fn to_json(&self) -> Value {
+        let mut json = self.event.to_json();
+        json["event_id"] = json!(self.event_id.to_string());
+        json
+    }
+
+    /// The event as sent to `POST /users/{id}/events`, where the nil id
+    /// stands for the local `root`.
+    pub fn to_server_json(&self, root: NodeId) -> Value {
+        Self {
+            event_id: self.event_id,
+            event: self.event.clone().to_shared_root(root),
+        }
+        .to_json()
+    }
+
+    /// Reads an outbox entry. Entries saved before events had ids get one.
+    pub fn from_json(value: &Value) -> Option<Self> {
+        let event = Event::from_json(value)?;
+        Some(match value["event_id"].as_str().map(Uuid::parse_str) {
+            Some(Ok(event_id)) => Self { event_id, event },
+            _ => Self::new(event),
+        })
+    }
+}
+
+/// An open `EventSource` on `/users/{id}/events/stream`, closed on drop.
+struct Subscription {
+    source: EventSource,
+    _on_message: Closure<dyn Fn(MessageEvent)>,
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        self.source.set_onmessage(None);
+        self.source.close();
+    }
+}
+
+/// Keeps the tree of an `EventLog` in sync with the server's events API.
+///
+/// Local events go to a persistent outbox and are pushed when the server is
+/// reachable. Remote events are folded into a base tree, and the pending
+/// local events are replayed on top of it to get the tree that is shown.
+/// They are streamed from the server as they are appended, and polled for
+/// in case the stream is down.
+#[derive(Clone, Copy)]
+pub struct SyncClient {
+    log: EventLog,
+    config: StoredValue<SyncConfig>,
+    pub status: RwSignal<SyncStatus>,
+    /// Local events of this tab not accepted by the server yet, oldest
+    /// first.
+    pub pending: RwSignal<Vec<Outgoing>>,
+    /// Ids of events the server is known to have, so that the outbox can
+    /// drop them whichever tab put them there.
+    settled: StoredValue<HashSet<Uuid>>,
+    cursor: StoredValue<i64>,
+    base: StoredValue<Value>,
+    busy: StoredValue<bool>,
+    again: StoredValue<bool>,
+    subscription: StoredValue<Option<Subscription>, LocalStorage>,
+}
+
+fn storage() -> Option<Storage> {
+    window().local_storage().ok().flatten()
+}
+
+pub(crate) fn load_item(key: &str) -> Option<Value> {
+    let json_string = storage()?.get_item(key).ok()??;
+    serde_json::from_str(&json_string).ok()
+}
+
+pub(crate) fn save_item(key: &str, value: &Value) {
+    if let Some(storage) = storage() {
+        let _ = storage.set_item(key, &value.to_string());
+    }
+}
+
+pub(crate) fn remove_item(key: &str) {
+    if let Some(storage) = storage() {
+        let _ = storage.remove_item(key);
+    }
+}
+
+/// Folds the rows of `GET /users/{id}/events` with an id greater than
+/// `after` into `base` and returns the id of the last one, or `None` if there
+/// were no such rows. Earlier rows were already folded in, e.g. by a pull
+/// that overlapped with the events stream. Rows with events this client does
+/// not know are skipped. Events of the nil id apply to the root of `base`.
+pub fn apply_remote(base: &mut NodeData, after: i64, rows: &[Value]) -> Option<i64> {
+    let mut cursor = None;
+    for row in rows {
+        if row["id"].as_i64().is_none_or(|id| id <= after) {
+            continue;
+        }
+        let event = row["type"]
+            .as_str()
+            .and_then(|type_name| Event::from_parts(type_name, &row["data"]))
+            .map(|event| event.from_shared_root(base.id));
+        if let Some(event) = event {
+            base.apply(&event);
+        }
+        cursor = row["id"].as_i64().or(cursor);
+    }
+    cursor
+}
+
+/// The event ids of `rows` from the server.
+fn delivered(rows: &[Value]) -> Vec<Uuid> {
+    rows.iter()
+        .filter_map(|row| Uuid::parse_str(row["event_id"].as_str()?).ok())
+        .collect()
+}
+
+/// Drops the `pending` events that are among `rows`: the server has them,
+/// even if the response to their push was lost, and they are in the base
+/// once the rows are folded into it. Returns whether any were dropped.
+pub fn remove_delivered(pending: &mut Vec<Outgoing>, rows: &[Value]) -> bool {
+    let delivered = delivered(rows);
+    let count = pending.len();
+    pending.retain(|outgoing| !delivered.contains(&outgoing.event_id));
+    pending.len() != count
+}
+
+/// The outbox shared by the tabs of a user, after one of them with the local
+/// events `pending` writes it: the `stored` events of other tabs that are not
+/// `settled` stay, in their order, followed by `pending`. Every tab writes the
+/// outbox, so none may drop events it did not push itself.
+pub fn merge_outbox(
+    stored: &[Outgoing],
+    settled: &HashSet<Uuid>,
+    pending: &[Outgoing],
+) -> Vec<Outgoing> {
+    stored
+        .iter()
+        .filter(|outgoing| {
+            !settled.contains(&outgoing.event_id)
+                && pending.iter().all(|own| own.event_id != outgoing.event_id)
+        })
+        .chain(pending)
+        .cloned()
+        .collect()
+}
+
+/// The tree to show: `base` with the `pending` local events on top. Events
+/// that no longer apply, e.g. an edit of a node removed remotely, are dropped
+/// silently by the reducer.
+pub fn rebase(base: &Value, pending: &[Outgoing]) -> Option<NodeData> {
+    let mut node = NodeData::from_json(base).ok()?;
+    for outgoing in pending {
+        node.apply(&outgoing.event);
+    }
+    Some(node)
+}
+
+/// Sends a JSON request, with `token` as a bearer token, and returns the JSON
+/// response, or `Null` if there is none. Error responses are reported with
+/// the server's message.
+pub(crate) async fn fetch_json(
+    method: &str,
+    url: &str,
+    token: Option<&str>,
+    body: Option<Value>,
+) -> Result<Value, SyncError> {
+    let failed = |err: JsValue| SyncError::Failed(format!("{:?}", err));
+
+    let init = RequestInit::new();
+    init.set_method(method);
+    if let Some(body) = body {
+        init.set_body(&JsValue::from_str(&body.to_string()));
+    }
+    let request = Request::new_with_str_and_init(url, &init).map_err(failed)?;
+    request
+        .headers()
+        .set("content-type", "application/json")
+        .map_err(failed)?;
+    if let Some(token) = token {
+        request
+            .headers()
+            .set("authorization", &format!("Bearer {}", token))
+            .map_err(failed)?;
+    }
+
+    // `fetch` only rejects when there is no response at all
+    let response = JsFuture::from(window().fetch_with_request(&request))
+        .await
+        .map_err(|_| SyncError::Offline)?;
+    let response: Response = response.dyn_into().map_err(failed)?;
+    let text = JsFuture::from(response.text().map_err(failed)?)
+        .await
+        .map_err(failed)?
+        .as_string()
+        .unwrap_or_default();
+    if response.status() == 401 && token.is_some() {
+        return Err(SyncError::LoggedOut);
+    }
+    if !response.ok() {
+        let message = serde_json::from_str::<Value>(&text)
+            .ok()
+            .and_then(|body| body["error"].as_str().map(str::to_string))
+            .unwrap_or_else(|| format!("{} {}", response.status(), response.status_text()));
+        return Err(SyncError::Failed(message));
+    }
+    if text.is_empty() {
+        return Ok(Value::Null);
+    }
+    serde_json::from_str(&text).map_err(|err| SyncError::Failed(err.to_string()))
+}
+
+impl SyncClient {
+    /// Restores the outbox and base from localStorage, records new events of
+    /// `log` from now on and starts syncing.
+    ///
+    /// The first time a client syncs there is no base yet. With `upload` set
+    /// the current tree is sent to the server as new nodes, otherwise it is
+    /// cleared right away and then filled with whatever the server has.
+    pub fn start(log: EventLog, config: SyncConfig, upload: bool) -> Self {
+        let client = Self {
+            log,
+            config: StoredValue::new(config),
+            status: RwSignal::new(SyncStatus::Syncing),
+            pending: RwSignal::new(Vec::new()),
+            settled: StoredValue::new(HashSet::new()),
+            cursor: StoredValue::new(0),
+            base: StoredValue::new(Value::Null),
+            busy: StoredValue::new(false),
+            again: StoredValue::new(false),
+            subscription: StoredValue::new_local(None),
+        };
+
+        client.adopt_legacy();
+        let pending = client.stored_outbox();
+        match load_item(&client.key(BASE_KEY)) {
+            Some(base) => {
+                client.base.set_value(base);
+                client.cursor.set_value(
+                    load_item(&client.key(CURSOR_KEY))
+                        .and_then(|c| c.as_i64())
+                        .unwrap_or(0),
+                );
+                client.pending.set(pending);
+                client.rebase();
+            }
+            None => {
+                let (empty, events) = log.with_tree(|tree| {
+                    let empty = NodeData::with_id(tree.id, tree.is_open, &tree.text, Vec::new());
+                    (empty, tree.to_events())
+                });
+                client.base.set_value(empty.to_json());
+                client.pending.set(if upload {
+                    events.into_iter().map(Outgoing::new).collect()
+                } else {
+                    pending
+                });
+                client.persist();
+                // Without an upload the tree shown has to go too, or edits of
+                // it would be pushed for nodes the server never gets
+                client.rebase();
+            }
+        }
+
+        let seen = StoredValue::new(log.events.with_untracked(Vec::len));
+        Effect::new(move |_| {
+            let new_events = log
+                .events
+                .with(|events| events[seen.get_value()..].to_vec());
+            if new_events.is_empty() {
+                return;
+            }
+            seen.update_value(|seen| *seen += new_events.len());
+            client
+                .pending
+                .update(|pending| pending.extend(new_events.into_iter().map(Outgoing::new)));
+            client.persist();
+            set_timeout(move || client.sync_now(), PUSH_DELAY);
+        });
+
+        let poll = set_interval_with_handle(move || client.sync_now(), POLL_INTERVAL).ok();
+        let online = window_event_listener(leptos::ev::online, move |_| {
+            client.sync_now();
+            client.subscribe_if_closed();
+        });
+        on_cleanup(move || {
+            if let Some(poll) = poll {
+                poll.clear();
+            }
+            online.remove();
+            client.subscription.set_value(None);
+        });
+        client.sync_now();
+        client.subscribe();
+        client
+    }
+
+    /// Opens the events stream from the cursor on. The browser reconnects by
+    /// itself after network errors, resuming after the last event it got.
+    /// An `EventSource` cannot send headers, so the token goes in the URL.
+    fn subscribe(self) {
+        let mut url = format!(
+            "{}?after={}",
+            self.url("events/stream"),
+            self.cursor.get_value()
+        );
+        if let Some(token) = self.config.with_value(|config| config.token.clone()) {
+            url.push_str(&format!("&access_token={}", token));
+        }
+        let Ok(source) = EventSource::new(&url) else {
+            return;
+        };
+        let on_message = Closure::<dyn Fn(MessageEvent)>::new(move |ev: MessageEvent| {
+            let rows = ev
+                .data()
+                .as_string()
+                .and_then(|text| serde_json::from_str::<Value>(&text).ok());
+            if let Some(Value::Array(rows)) = rows {
+                self.receive(&rows);
+            }
+        });
+        source.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+        self.subscription.set_value(Some(Subscription {
+            source,
+            _on_message: on_message,
+        }));
+    }
+
+    /// Reopens the events stream if the browser gave up on it, which it does
+    /// when the server answers with an error.
+    fn subscribe_if_closed(self) {
+        let closed = self.subscription.with_value(|subscription| {
+            subscription
+                .as_ref()
+                .is_none_or(|subscription| subscription.source.ready_state() == EventSource::CLOSED)
+        });
+        if closed {
+            self.subscribe();
+        }
+    }
+
+    /// Applies rows from the events stream. While a sync is running they are
+    /// left to the pull after it, which also fetches them, so that they are
+    /// not mixed with a push whose events have not left the outbox yet.
+    fn receive(self, rows: &[Value]) {
+        if self.stopped() || self.status.get_untracked() == SyncStatus::LoggedOut {
+            return;
+        }
+        if self.busy.get_value() {
+            self.again.set_value(true);
+            return;
+        }
+        if let Err(SyncError::Failed(message)) = self.fold(rows) {
+            self.status.set(SyncStatus::Failed(message));
+        }
+    }
+
+    fn url(&self, path: &str) -> String {
+        self.config.with_value(|config| {
+            format!(
+                "{}/users/{}/{}",
+                config.server_url.trim_end_matches('/'),
+                config.user_id,
+                path
+            )
+        })
+    }
+
+    /// The localStorage key `name` of the configured user.
+    fn key(&self, name: &str) -> String {
+        format!(
+            "{}_{}",
+            name,
+            self.config.with_value(|config| config.user_id)
+        )
+    }
+
+    /// Moves the outbox, cursor and base that earlier versions saved under
+    /// keys without a user id over to the configured user, who is the first
+    /// to log in since, unless they have their own already. The old keys are
+    /// removed either way.
+    fn adopt_legacy(&self) {
+        let adopt = load_item(&self.key(BASE_KEY)).is_none();
+        for name in [OUTBOX_KEY, CURSOR_KEY, BASE_KEY] {
+            let Some(value) = load_item(name) else {
+                continue;
+            };
+            if adopt {
+                save_item(&self.key(name), &value);
+            }
+            remove_item(name);
+        }
+    }
+
+    fn token(&self) -> Option<String> {
+        self.config.with_value(|config| config.token.clone())
+    }
+
+    fn stored_outbox(&self) -> Vec<Outgoing> {
+        load_item(&self.key(OUTBOX_KEY))
+            .and_then(|outbox| {
+                outbox
+                    .as_array()
+                    .map(|events| events.iter().filter_map(Outgoing::from_json).collect())
+            })
+            .unwrap_or_default()
+    }
+
+    /// The local events of all tabs, as they are to be saved. Other tabs with
+    /// the same user write the outbox too, and push their own events.
+    fn outbox(&self) -> Vec<Outgoing> {
+        let stored = self.stored_outbox();
+        self.pending.with_untracked(|pending| {
+            self.settled
+                .with_value(|settled| merge_outbox(&stored, settled, pending))
+        })
+    }
+
+    fn persist(&self) {
+        let outbox = self.outbox().iter().map(Outgoing::to_json).collect();
+        save_item(&self.key(OUTBOX_KEY), &Value::Array(outbox));
+        save_item(&self.key(CURSOR_KEY), &Value::from(self.cursor.get_value()));
+        self.base
+            .with_value(|base| save_item(&self.key(BASE_KEY), base));
+    }
+
+    /// Shows the base with the local events of all tabs on top, which the
+    /// other tabs have applied here already.
+    fn rebase(&self) {
+        let outbox = self.outbox();
+        let live = self.base.with_value(|base| rebase(base, &outbox));
+        if let Some(live) = live {
+            self.log.reset(live);
+        }
+    }
+
+    /// Pushes the outbox, then pulls remote events. A request while a sync
+    /// is running is remembered and runs once the current one is done.
+    pub fn sync_now(self) {
+        if self.stopped() || self.status.get_untracked() == SyncStatus::LoggedOut {
+            return;
+        }
+        if self.busy.get_value() {
+            self.again.set_value(true);
+            return;
+        }
+        self.busy.set_value(true);
+        self.status.set(SyncStatus::Syncing);
+
+        spawn_local(async move {
+            let result = match self.push().await {
+                Ok(()) => self.pull().await,
+                Err(err) => Err(err),
+            };
+            if self.stopped() {
+                return;
+            }
+            self.status.set(match result {
+                Ok(()) => SyncStatus::Synced,
+                Err(SyncError::Offline) => SyncStatus::Offline,
+                Err(SyncError::LoggedOut) => SyncStatus::LoggedOut,
+                Err(SyncError::Failed(message)) => SyncStatus::Failed(message),
+            });
+            if self.status.get_untracked() == SyncStatus::LoggedOut {
+                self.subscription.set_value(None);
+            }
+            self.busy.set_value(false);
+            if self.again.get_value() {
+                self.again.set_value(false);
+                self.sync_now();
+            }
+        });
+    }
+
+    /// Whether the owner of this client is gone, e.g. because another
+    /// document was opened. A push that finishes after that leaves its events
+    /// in the outbox. They are pushed again with the same ids, which the
+    /// server skips if it has them already.
+    fn stopped(self) -> bool {
+        self.busy.try_get_value().is_none()
+    }
+
+    async fn push(self) -> Result<(), SyncError> {
+        let root = self.log.with_tree(|tree| tree.id);
+        let (batch, sent): (Vec<Value>, Vec<Uuid>) = self.pending.with_untracked(|pending| {
+            pending
+                .iter()
+                .map(|outgoing| (outgoing.to_server_json(root), outgoing.event_id))
+                .unzip()
+        });
+        if batch.is_empty() {
+            return Ok(());
+        }
+
+        fetch_json(
+            "POST",
+            &self.url("events"),
+            self.token().as_deref(),
+            Some(Value::Array(batch)),
+        )
+        .await?;
+        if self.stopped() {
+            return Ok(());
+        }
+        // Events added while the request was in flight stay in the outbox
+        self.pending
+            .update(|pending| pending.retain(|outgoing| !sent.contains(&outgoing.event_id)));
+        self.settled.update_value(|settled| settled.extend(sent));
+        self.persist();
+        Ok(())
+    }
+
+    async fn pull(self) -> Result<(), SyncError> {
+        let url = format!("{}?after={}", self.url("events"), self.cursor.get_value());
+        let rows = fetch_json("GET", &url, self.token().as_deref(), None).await?;
+        if self.stopped() {
+            return Ok(());
+        }
+        self.fold(rows.as_array().map(Vec::as_slice).unwrap_or_default())
+    }
+
+    /// Folds remote rows into the base and shows them with the pending local
+    /// events on top.
+    fn fold(self, rows: &[Value]) -> Result<(), SyncError> {
+        if rows.is_empty() {
+            return Ok(());
+        }
+        let mut base = self
+            .base
+            .with_value(NodeData::from_json)
+            .map_err(|err| SyncError::Failed(format!("invalid base in localStorage: {}", err)))?;
+        let Some(cursor) = apply_remote(&mut base, self.cursor.get_value(), rows) else {
+            return Ok(());
+        };
+        // Events pushed before, whose response did not arrive, are not
+        // replayed on top of themselves
+        self.pending.update(|pending| {
+            remove_delivered(pending, rows);
+        });
+        self.settled
+            .update_value(|settled| settled.extend(delivered(rows)));
+        self.cursor.set_value(cursor);
+        self.base.set_value(base.to_json());
+        self.persist();
+        self.rebase();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn texts(node: &NodeData) -> Vec<&str> {
+        node.children
+            .iter()
+            .map(|child| child.text.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn test_apply_remote_advances_cursor() {
+        let mut base = NodeData::new(false, "root", vec![]);
+        let rows = json!([
+            { "id": 4, "type": "Added", "data": { "parent": null, "text": "make lunch", "id": "8722655e-f231-11ef-8932-1f1e2ee24d96" } },
+            { "id": 5, "type": "Unknown", "data": {} },
+            { "id": 7, "type": "Edited", "data": { "text": "make pasta for lunch", "id": "8722655e-f231-11ef-8932-1f1e2ee24d96" } }
+        ]);
+
+        assert_eq!(
+            apply_remote(&mut base, 0, rows.as_array().unwrap()),
+            Some(7)
+        );
+        assert_eq!(texts(&base), ["make pasta for lunch"]);
+        assert_eq!(apply_remote(&mut base, 7, &[]), None);
+
+        // Rows up to the cursor were folded in already
+        let rows = json!([
+            { "id": 7, "type": "Edited", "data": { "text": "make lunch again", "id": "8722655e-f231-11ef-8932-1f1e2ee24d96" } },
+            { "id": 8, "type": "MarkedAsDone", "data": { "id": "8722655e-f231-11ef-8932-1f1e2ee24d96" } }
+        ]);
+        assert_eq!(
+            apply_remote(&mut base, 7, rows.as_array().unwrap()),
+            Some(8)
+        );
+        assert_eq!(texts(&base), ["make pasta for lunch"]);
+        assert!(base.children[0].done);
+        assert_eq!(apply_remote(&mut base, 8, rows.as_array().unwrap()), None);
+    }
+
+    #[test]
+    fn test_rebase_replays_pending_on_remote() {
+        let remote = NodeData::next_id();
+        let local = NodeData::next_id();
+        let mut base = NodeData::new(false, "root", vec![]);
+        base.apply(&Event::Added {
+            id: remote,
+            parent: None,
+            index: 0,
+            after: None,
+            text: "remote".to_string(),
+        });
+
+        let pending: Vec<Outgoing> = [
+            Event::Added {
+                id: local,
+                parent: None,
+                index: 1,
+                after: None,
+                text: "local".to_string(),
+            },
+            Event::Edited {
+                id: remote,
+                text: "remote, edited locally".to_string(),
+                from: None,
+            },
+            // Removed remotely in the meantime
+            Event::Edited {
+                id: NodeId::nil(),
+                text: "gone".to_string(),
+                from: None,
+            },
+        ]
+        .into_iter()
+        .map(Outgoing::new)
+        .collect();
+
+        let live = rebase(&base.to_json(), &pending).unwrap();
+        assert_eq!(texts(&live), ["remote, edited locally", "local"]);
+        assert_eq!(live.id, base.id);
+        // The base itself is untouched
+        assert_eq!(texts(&base), ["remote"]);
+    }
+
+    #[test]
+    fn test_batch_delivered_twice() {
+        let lunch = NodeData::next_id();
+        let mut base = NodeData::new(false, "root", vec![]);
+        base.apply(&Event::Added {
+            id: lunch,
+            parent: None,
+            index: 0,
+            after: None,
+            text: "a".to_string(),
+        });
+        let edit = |from: &str, text: &str| {
+            Outgoing::new(Event::Edited {
+                id: lunch,
+                text: text.to_string(),
+                from: Some(from.to_string()),
+            })
+        };
+        let mut pending = vec![edit("a", "ab"), edit("ab", "abc")];
+
+        // The server stored the batch but its response was lost, so it stays
+        // in the outbox and is pushed again under the same ids
+        let outbox = Value::Array(pending.iter().map(Outgoing::to_json).collect());
+        let restored: Vec<Outgoing> = outbox
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter_map(Outgoing::from_json)
+            .collect();
+        assert_eq!(restored, pending);
+
+        // Replayed on top of itself once it comes back, the batch would
+        // merge "abc" into "abcbc"
+        let rows: Vec<Value> = pending
+            .iter()
+            .zip(2..)
+            .map(|(outgoing, id)| {
+                let mut row = outgoing.to_json();
+                row["id"] = json!(id);
+                row
+            })
+            .collect();
+        assert_eq!(apply_remote(&mut base, 1, &rows), Some(3));
+        let twice = rebase(&base.to_json(), &pending).unwrap();
+        assert_eq!(texts(&twice), ["abcbc"]);
+
+        assert!(remove_delivered(&mut pending, &rows));
+        assert!(pending.is_empty());
+        let live = rebase(&base.to_json(), &pending).unwrap();
+        assert_eq!(texts(&live), ["abc"]);
+        assert!(!remove_delivered(&mut pending, &rows));
+
+        // Outbox entries saved before events had ids get one
+        let old = Outgoing::from_json(&restored[0].event.to_json()).unwrap();
+        assert_eq!(old.event, restored[0].event);
+        assert_ne!(old.event_id, restored[0].event_id);
+    }
+
+    #[test]
+    fn test_merge_outbox_of_two_tabs() {
+        let added = |text: &str| {
+            Outgoing::new(Event::Added {
+                id: NodeData::next_id(),
+                parent: None,
+                index: 0,
+                after: None,
+                text: text.to_string(),
+            })
+        };
+        let (a1, a2, b1) = (added("a1"), added("a2"), added("b1"));
+
+        // Tab A wrote its event, then tab B, offline as well, writes its own
+        // without losing A's
+        let stored = merge_outbox(&[], &HashSet::new(), std::slice::from_ref(&a1));
+        let stored = merge_outbox(&stored, &HashSet::new(), std::slice::from_ref(&b1));
+        assert_eq!(stored, [a1.clone(), b1.clone()]);
+
+        // A adds another event; its own events are not listed twice
+        let stored = merge_outbox(&stored, &HashSet::new(), &[a1.clone(), a2.clone()]);
+        assert_eq!(stored, [b1.clone(), a1.clone(), a2.clone()]);
+
+        // Once A pushed its events, B's remain until the server has them
+        let settled: HashSet<Uuid> = [a1.event_id, a2.event_id].into();
+        let stored = merge_outbox(&stored, &settled, &[]);
+        let settled: HashSet<Uuid> = [b1.event_id].into();
+        assert_eq!(stored, [b1]);
+        assert!(merge_outbox(&stored, &settled, &[]).is_empty());
+    }
+
+    #[test]
+    fn test_root_events_are_shared() {
+        // Two devices, each with a root of its own id
+        let mut here = NodeData::new(false, "", vec![]);
+        let mut there = NodeData::new(false, "", vec![]);
+        let edited = Outgoing::new(Event::Edited {
+            id: here.id,
+            text: "Notes".to_string(),
+            from: None,
+        });
+
+        let mut row = edited.to_server_json(here.id);
+        assert_eq!(row["data"]["id"], NodeId::nil().to_string());
+        row["id"] = json!(1);
+        assert_eq!(apply_remote(&mut there, 0, &[row.clone()]), Some(1));
+        assert_eq!(there.text, "Notes");
+        // The device that made it gets it back for its own root
+        assert_eq!(apply_remote(&mut here, 0, &[row]), Some(1));
+        assert_eq!(here.text, "Notes");
+        // The outbox keeps the local id, so the event replays locally
+        assert_eq!(Outgoing::from_json(&edited.to_json()), Some(edited));
+    }
+}
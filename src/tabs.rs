@@ -0,0 +1,169 @@
+use leptos::prelude::*;
+use leptos::web_sys::{BroadcastChannel, MessageEvent};
+use serde_json::{json, Value};
+use wasm_bindgen::closure::Closure;
+use wasm_bindgen::{JsCast, JsValue};
+
+use crate::models::{Event, EventLog, NodeData};
+
+/// Prefix of the `BroadcastChannel` of a document, followed by its key.
+const CHANNEL_PREFIX: &str = "notes-tabs-";
+
+/// What tabs showing the same document tell each other.
+#[derive(Clone, Debug, PartialEq)]
+pub enum TabMessage {
+    /// Events logged in the sending tab.
+    Events(Vec<Event>),
+    /// A tab opened the document and asks for the current tree.
+    Hello,
+    /// The tree of the sending tab, in answer to `Hello`. It may have
+    /// changes that are not saved yet.
+    Tree(Value),
+}
+
+impl TabMessage {
+    pub fn to_json(&self) -> Value {
+        match self {
+            TabMessage::Events(events) => json!({
+                "type": "events",
+                "events": events.iter().map(Event::to_json).collect::<Vec<_>>()
+            }),
+            TabMessage::Hello => json!({ "type": "hello" }),
+            TabMessage::Tree(tree) => json!({ "type": "tree", "tree": tree }),
+        }
+    }
+
+    /// Reads a message, dropping events this tab does not know.
+    pub fn from_json(value: &Value) -> Option<Self> {
+        match value["type"].as_str()? {
+            "events" => Some(TabMessage::Events(
+                value["events"]
+                    .as_array()?
+                    .iter()
+                    .filter_map(Event::from_json)
+                    .collect(),
+            )),
+            "hello" => Some(TabMessage::Hello),
+            "tree" => Some(TabMessage::Tree(value["tree"].clone())),
+            _ => None,
+        }
+    }
+}
+
+/// Keeps the tree of an `EventLog` in step with other tabs showing the same
+/// document, so that their edits do not overwrite each other on save.
+///
+/// Events logged here are broadcast, and events from other tabs are applied
+/// without being logged. Fold state stays local to each tab.
+#[derive(Clone, Copy)]
+pub struct TabSync {
+    log: EventLog,
+    channel: StoredValue<BroadcastChannel, LocalStorage>,
+}
+
+impl TabSync {
+    /// Joins the channel of the document with `key` and asks the other tabs
+    /// for their tree. `None` if the browser has no `BroadcastChannel`.
+    pub fn start(log: EventLog, key: &str) -> Option<Self> {
+        let channel = BroadcastChannel::new(&format!("{}{}", CHANNEL_PREFIX, key)).ok()?;
+        let tabs = Self {
+            log,
+            channel: StoredValue::new_local(channel.clone()),
+        };
+
+        let on_message = Closure::<dyn Fn(MessageEvent)>::new(move |ev: MessageEvent| {
+            let message = ev
+                .data()
+                .as_string()
+                .and_then(|text| serde_json::from_str(&text).ok())
+                .and_then(|value| TabMessage::from_json(&value));
+            if let Some(message) = message {
+                tabs.receive(message);
+            }
+        });
+        channel.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+        let on_message = StoredValue::new_local(Some(on_message));
+
+        let seen = StoredValue::new(log.events.with_untracked(Vec::len));
+        Effect::new(move |_| {
+            let new_events = log
+                .events
+                .with(|events| events[seen.get_value()..].to_vec());
+            if new_events.is_empty() {
+                return;
+            }
+            seen.update_value(|seen| *seen += new_events.len());
+            tabs.post(&TabMessage::Events(new_events));
+        });
+
+        on_cleanup(move || {
+            tabs.channel.with_value(|channel| {
+                channel.set_onmessage(None);
+                channel.close();
+            });
+            on_message.set_value(None);
+        });
+        tabs.post(&TabMessage::Hello);
+        Some(tabs)
+    }
+
+    fn post(self, message: &TabMessage) {
+        let data = JsValue::from_str(&message.to_json().to_string());
+        self.channel.with_value(|channel| {
+            let _ = channel.post_message(&data);
+        });
+    }
+
+    fn receive(self, message: TabMessage) {
+        match message {
+            TabMessage::Events(events) => {
+                for event in events {
+                    if !matches!(event, Event::Folded { .. }) {
+                        self.log.apply(&event);
+                    }
+                }
+            }
+            TabMessage::Hello => {
+                let tree = self.log.with_tree(NodeData::to_json);
+                self.post(&TabMessage::Tree(tree));
+            }
+            TabMessage::Tree(tree) => {
+                if let Ok(tree) = NodeData::from_json(&tree) {
+                    self.log.reset(tree);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_message_json_roundtrip() {
+        let id = NodeData::next_id();
+        let messages = [
+            TabMessage::Events(vec![
+                Event::Edited {
+                    id,
+                    text: "typed in another tab".to_string(),
+                    from: None,
+                },
+                Event::Removed { id },
+            ]),
+            TabMessage::Hello,
+            TabMessage::Tree(NodeData::new(true, "root", vec![]).to_json()),
+        ];
+        for message in messages {
+            assert_eq!(TabMessage::from_json(&message.to_json()), Some(message));
+        }
+
+        let unknown = json!({ "type": "events", "events": [{ "type": "Unknown", "data": {} }] });
+        assert_eq!(
+            TabMessage::from_json(&unknown),
+            Some(TabMessage::Events(vec![]))
+        );
+        assert_eq!(TabMessage::from_json(&json!({ "type": "bye" })), None);
+    }
+}